@@ -337,6 +337,13 @@ pub enum Instruction {
     SetOrigin(Option<Address>),
     Word(Immediate),
     PopProgramCounterSubroutine,
+    SetInterruptEnable,
+    ClearInterruptEnable,
+    ReturnFromInterrupt,
+    /// the two byte address of a label (high byte first), for filling in vectors
+    LabelAddress(Label),
+    /// marks where the next instruction came from (file, line number starting at 1), doesn't produce any bytes
+    SourceLine(String, u32),
 }
 // todo: calculate the indices of the define uses in the assembler so the locations are accurate
 pub fn preprocess(directory: &str, filename: &str, symbol_table: SymbolTable) -> (String, SymbolTable) {
@@ -370,6 +377,8 @@ pub fn preprocess(directory: &str, filename: &str, symbol_table: SymbolTable) ->
     let mut result = Vec::new();
     for (index, raw_line) in content.lines().enumerate() {
         if raw_line.contains("#define") {
+            // keep the line so line numbers still match the source file
+            result.push("\n");
             continue;
         }
         let split_line = raw_line.split_once('*').unwrap_or(("", ""));
@@ -410,12 +419,42 @@ pub fn postprocess(instructions: Vec<Instruction>, symbol_table: SymbolTable, in
         for symbol_use in included_table.symbol_uses.into_iter() {
             final_symbol_table.symbol_uses.insert(symbol_use.0, symbol_use.1);
         }
+        for define in included_table.defines.into_iter() {
+            final_symbol_table.defines.insert(define.0, define.1);
+        }
     }
 
     (final_instructions, final_symbol_table)
 }
 
+/// a `.test` block in a source file, which is only assembled by `goldasm test` (see doc/Testing.md)
+#[derive(Debug, PartialEq, Clone)]
+pub struct SourceTest {
+    pub name: String,
+    /// the subroutine the block is assembled into, without the _SR
+    pub subroutine: String,
+    /// everything after each `.expect` (the target, then the value)
+    pub expects: Vec<String>,
+}
+
+/// the subroutine the nth `.test` block in a file is assembled into
+/// it's lowercase so it can't be the same as a label in the file
+fn test_subroutine(module_name_dot: &str, index: usize) -> String {
+    format!("{module_name_dot}test_{index}")
+}
+
+/// parses a file and everything it includes, leaving out `.test` blocks
 pub fn parse(directory: &str, filename: &str, symbol_table: SymbolTable) -> (Vec<Instruction>, Includes, SymbolTable) {
+    let (instructions, includes, symbol_table, _) = parse_file(directory, filename, symbol_table, false);
+    (instructions, includes, symbol_table)
+}
+/// like parse, but with the file's `.test` blocks as subroutines (the files it includes still leave theirs out),
+/// and the tests that are in them
+pub fn parse_with_tests(directory: &str, filename: &str, symbol_table: SymbolTable) -> (Vec<Instruction>, Includes, SymbolTable, Vec<SourceTest>) {
+    parse_file(directory, filename, symbol_table, true)
+}
+
+fn parse_file(directory: &str, filename: &str, symbol_table: SymbolTable, with_tests: bool) -> (Vec<Instruction>, Includes, SymbolTable, Vec<SourceTest>) {
     let (content, symbol_table) = preprocess(directory, filename, symbol_table);
     println!("INFO: Parsing file {}", directory.to_string() + filename);
 
@@ -423,13 +462,61 @@ pub fn parse(directory: &str, filename: &str, symbol_table: SymbolTable) -> (Vec
     
     let mut instructions: Vec<Instruction> = Vec::new();
     let mut includes = Includes::new();
+    let mut tests: Vec<SourceTest> = Vec::new();
+    // whether we're between a .test and its .endtest (which is always the last test)
+    let mut in_test = false;
     
-    for raw_line in content.lines() {
+    for (line_index, raw_line) in content.lines().enumerate() {
         // strip out leading and trailing whitespace, as well as comments
         let line = raw_line.splitn(2, "//").next().unwrap_or("").trim();
         if line.is_empty() {
             continue;
         }
+
+        // test block logic
+        match line.split_whitespace().next() {
+            Some(".test") => {
+                if in_test {
+                    panic!("Tests can't be inside other tests! ({directory}{filename}:{})", line_index + 1);
+                }
+                let name = line.strip_prefix(".test").unwrap().trim();
+                if name.is_empty() {
+                    panic!("Tests need a name! ({directory}{filename}:{})", line_index + 1);
+                }
+                let subroutine = test_subroutine(module_name_dot, tests.len());
+                if with_tests {
+                    instructions.push(Instruction::Subroutine(subroutine.clone()));
+                }
+                tests.push(SourceTest {
+                    name: name.to_string(),
+                    subroutine,
+                    expects: Vec::new(),
+                });
+                in_test = true;
+                continue;
+            }
+            Some(".endtest") => {
+                if !in_test {
+                    panic!("Found a .endtest without a .test! ({directory}{filename}:{})", line_index + 1);
+                }
+                if with_tests {
+                    let subroutine = &tests.last().unwrap().subroutine;
+                    instructions.push(Instruction::ReturnFromSubroutine(Subroutine { name: subroutine.clone() + "_EndSR" }));
+                }
+                in_test = false;
+                continue;
+            }
+            Some(".expect") => {
+                if !in_test {
+                    panic!("Found a .expect outside of a test! ({directory}{filename}:{})", line_index + 1);
+                }
+                // these are checked by the test runner, not assembled
+                tests.last_mut().unwrap().expects.push(line.strip_prefix(".expect").unwrap().trim().to_string());
+                continue;
+            }
+            _ if in_test && !with_tests => continue,
+            _ => {}
+        }
         
         if line.contains("#include") {
             includes.parse_include(line, directory);
@@ -495,8 +582,21 @@ pub fn parse(directory: &str, filename: &str, symbol_table: SymbolTable) -> (Vec
             instructions.push(Instruction::Word(Immediate::from_str(words[1])));
             continue;
         }
+
+        // label address logic
+        if line.contains(".addr") {
+            let name = words[1].strip_prefix('~').expect("The address in a .addr should be a label (like ~HANDLER)!");
+            if name.contains('.') {
+                // in another file, don't add our filename
+                instructions.push(Instruction::LabelAddress(Label { name: name.to_string() }));
+            } else {
+                instructions.push(Instruction::LabelAddress(Label { name: module_name_dot.to_string() + name }));
+            }
+            continue;
+        }
         
         // normal instruction
+        instructions.push(Instruction::SourceLine(filename.to_string(), line_index as u32 + 1));
         match words[0].trim().to_lowercase().as_str() {
             "noop" => instructions.push(Instruction::Noop),
             "add" => {
@@ -896,6 +996,9 @@ pub fn parse(directory: &str, filename: &str, symbol_table: SymbolTable) -> (Vec
             },
             "phpc" => instructions.push(Instruction::PushProgramCounter),
             "plpc" => instructions.push(Instruction::PopProgramCounter),
+            "sie" => instructions.push(Instruction::SetInterruptEnable),
+            "cie" => instructions.push(Instruction::ClearInterruptEnable),
+            "rti" => instructions.push(Instruction::ReturnFromInterrupt),
             "//" => continue,
             "" => continue,
             _ => {
@@ -903,7 +1006,10 @@ pub fn parse(directory: &str, filename: &str, symbol_table: SymbolTable) -> (Vec
             }
         }
     }
-    (instructions, includes, symbol_table)
+    if in_test {
+        panic!("A .test is missing its .endtest! ({directory}{filename})");
+    }
+    (instructions, includes, symbol_table, tests)
 }
 
 fn parse_register_or_2_register_instruction(words: Vec<&str>) -> (Option<Register>, Option<(Register, Register)>) {
@@ -1,7 +1,7 @@
 use std::io::Write;
 use crossterm::style::Stylize;
 use crate::asm_parser;
-use crate::asm_parser::{AddressMode, Instruction};
+use crate::asm_parser::{Address, AddressMode, Immediate, Instruction, NonZeroPageAddress, SourceTest};
 use crate::disassembler::symbols::{SymbolTable};
 /*
 Assembling overview:
@@ -580,6 +580,15 @@ pub fn assemble(instructions: Vec<Instruction>, size: u16, mut symbol_table: Sym
             Instruction::PopProgramCounterSubroutine => {
                 insert(&mut binary_instructions, 0x57, &mut target_address);
             }
+            Instruction::SetInterruptEnable => {
+                insert(&mut binary_instructions, 0x5C, &mut target_address);
+            }
+            Instruction::ClearInterruptEnable => {
+                insert(&mut binary_instructions, 0x5D, &mut target_address);
+            }
+            Instruction::ReturnFromInterrupt => {
+                insert(&mut binary_instructions, 0x5E, &mut target_address);
+            }
             // -------------------- assembler directives --------------------
             Instruction::Label(name) => {
                 let name = name.rsplit_once('/').unwrap_or(("", name.as_str())).1.to_string();
@@ -588,9 +597,24 @@ pub fn assemble(instructions: Vec<Instruction>, size: u16, mut symbol_table: Sym
                     address: target_address as u16
                 });
             }
+            Instruction::SourceLine(file, line) => {
+                symbol_table.add_source_line(target_address as u16, file, line);
+            }
             Instruction::Word(value) => {
                 insert(&mut binary_instructions, value.value.to_decimal() as u8, &mut target_address);
             }
+            Instruction::LabelAddress(label) => {
+                // label uses point at the byte before the address, which is usually the opcode
+                let address_index = (target_address as u16).checked_sub(1)
+                    .unwrap_or_else(|| panic!("A .addr can't be at 0000, since there's no byte before it to point at (~{})!", label.name));
+                label_uses.push(AssemblerLabelUse {
+                    name: label.name,
+                    address_index,
+                    instruction_index: target_address as u16
+                });
+                insert(&mut binary_instructions, 0x00, &mut target_address);
+                insert(&mut binary_instructions, 0x00, &mut target_address);
+            }
             Instruction::SetOrigin(address) => {
                 if let Some(address) = address {
                     // todo: check if things overlap in this code path as well as in the parameterless .org
@@ -611,7 +635,8 @@ pub fn assemble(instructions: Vec<Instruction>, size: u16, mut symbol_table: Sym
             }
             _ => eprintln!("{}", format!("ERROR: Unimplemented instruction! ({instruction:?})").red().bold())
         }
-        if target_address > max_address {
+        // target_address is where the next byte goes, so it can be one past the last byte we can insert at
+        if target_address > max_address + 1 {
             panic!("Tried to overwrite code inside the binary (check your .org's)!");
         }
     }
@@ -670,6 +695,49 @@ fn append(array: &mut [u8], values: &mut [u8], index: &mut usize) {
     }
 }
 
+/// splits a path to a source file into its directory (ending with a separator) and file name
+pub fn split_path(target_file: &str) -> (String, String) {
+    if cfg!(target_os = "linux") || !target_file.contains('\\') {
+        // linux or windows with forward slashes
+        let directory = target_file.rsplitn(2, '/').nth(1).unwrap_or(".").to_string() + "/";
+        (directory, target_file.rsplitn(2, '/').next().unwrap().to_string())
+    } else if cfg!(target_os = "windows") {
+        // windows with backslashes
+        let directory = target_file.rsplitn(2, '\\').nth(1).unwrap_or(".").to_string() + "\\";
+        (directory, target_file.rsplitn(2, '\\').next().unwrap().to_string())
+    } else {
+        panic!("Only linux and windows are supported!");
+    }
+}
+
+/// parses and assembles a source file and everything it includes, without writing anything
+pub fn assemble_file(directory: &str, filename: &str, size: u16) -> (Vec<u8>, SymbolTable) {
+    let parsed_values = asm_parser::parse(directory, filename, SymbolTable::new());
+    let instructions = asm_parser::postprocess(parsed_values.0, parsed_values.2, parsed_values.1);
+    assemble(instructions.0, size, instructions.1)
+}
+
+/// assembles a source file with its `.test` blocks, as a program that only has the file in it
+/// the file goes at 0200 with the end loop at FFF8 and the reset vector at FFFC, like the examples
+/// returns the tests that were found too, so they can be run
+pub fn assemble_tests(directory: &str, filename: &str, size: u16) -> (Vec<u8>, SymbolTable, Vec<SourceTest>) {
+    let (parsed_instructions, includes, symbol_table, tests) = asm_parser::parse_with_tests(directory, filename, SymbolTable::new());
+    let mut instructions = vec![Instruction::SetOrigin(Some(Address::from_str("%0200")))];
+    instructions.extend(parsed_instructions);
+    instructions.extend([
+        Instruction::SetOrigin(Some(Address::from_str("%FFF8"))),
+        Instruction::Jump(Some(NonZeroPageAddress::from_str("%FFF8")), None),
+        Instruction::SetOrigin(Some(Address::from_str("%FFFC"))),
+        Instruction::Word(Immediate::from_str("#02")),
+        Instruction::Word(Immediate::from_str("#00")),
+        // the includes go after the file
+        Instruction::SetOrigin(None),
+    ]);
+    let instructions = asm_parser::postprocess(instructions, symbol_table, includes);
+    let (binary, symbol_table) = assemble(instructions.0, size, instructions.1);
+    (binary, symbol_table, tests)
+}
+
 pub fn write(data: &[u8], directory: &str, filename: &str) {
     let file = std::fs::OpenOptions::new()
         .write(true)
@@ -150,6 +150,15 @@ pub fn disassemble(instructions: Vec<Instruction>, bytes_to_skip: Vec<u8>) -> Ve
             Instruction::PopProgramCounterSubroutine => {
                 result.push("rts".to_string());
             }
+            Instruction::SetInterruptEnable => {
+                result.push("sie".to_string());
+            }
+            Instruction::ClearInterruptEnable => {
+                result.push("cie".to_string());
+            }
+            Instruction::ReturnFromInterrupt => {
+                result.push("rti".to_string());
+            }
         }
         let num_to_skip = bytes_to_skip[index];
         for _ in 0..num_to_skip {
@@ -7,12 +7,20 @@ pub struct SymbolTable {
     pub symbols: HashMap<u16, Symbol>,
     // the u16 is the index of the line in the binary
     pub symbol_uses: HashMap<u16, Symbol>,
+    // defines all share the same address key in symbols, so they're also stored by name
+    #[serde(default)]
+    pub defines: HashMap<String, Symbol>,
+    // address of the first byte of an instruction -> where it came from
+    #[serde(default)]
+    pub lines: HashMap<u16, SourceLine>,
 }
 impl SymbolTable {
     pub fn new() -> Self {
         SymbolTable {
             symbols: HashMap::new(),
             symbol_uses: HashMap::new(),
+            defines: HashMap::new(),
+            lines: HashMap::new(),
         }
     }
     pub fn to_bytes(&self) -> Vec<u8> {
@@ -22,12 +30,60 @@ impl SymbolTable {
         rmp_serde::from_slice(bytes).expect("Failed to deserialize symbol table!")
     }
     pub fn add_define(&mut self, define: asm_parser::Define) {
+        self.defines.insert(define.name.clone(), Symbol {
+            name: define.name.clone(),
+            value: define.value.clone(),
+            symbol_type: SymbolType::Define,
+        });
         self.symbols.insert(define.value.parse().unwrap_or(0), Symbol {
             name: define.name,
             value: define.value,
             symbol_type: SymbolType::Define,
         });
     }
+    /// finds the address of a define or label by name
+    /// folder names are ignored, so `bank.p1_score_low` and `lib/math.RESULT_LOW` both work,
+    /// and the module name can be left off if there's no full match (`RESULT_LOW`)
+    pub fn resolve(&self, name: &str) -> Option<u16> {
+        let name = strip_folders(name);
+        self.resolve_with(|symbol_name| strip_folders(symbol_name) == name)
+            .or_else(|| self.resolve_with(|symbol_name| strip_module(symbol_name) == name))
+    }
+    /// labels are tried before defines, and the first by address or name wins, so the result doesn't change between runs
+    fn resolve_with(&self, matches: impl Fn(&str) -> bool) -> Option<u16> {
+        let label = self.symbols.iter()
+            .filter(|(_, symbol)| symbol.symbol_type == SymbolType::Label && matches(&symbol.name))
+            .map(|(address, _)| *address)
+            .min();
+        // a define with no address (like a value) is skipped, so a label or another define can still match
+        label.or_else(|| self.defines.values()
+            .filter(|define| matches(&define.name))
+            .filter_map(|define| Some((&define.name, define_address(define)?)))
+            .min()
+            .map(|(_, address)| address))
+    }
+    /// finds the name of the define that points at the given address, if there is one
+    pub fn define_at(&self, address: u16) -> Option<String> {
+        let mut names = self.defines.values()
+            .filter(|define| define_address(define) == Some(address))
+            .map(|define| strip_folders(&define.name).to_string())
+            .collect::<Vec<String>>();
+        // multiple files can define the same address, so keep it stable between frames
+        names.sort();
+        names.into_iter().next()
+    }
+    pub fn add_source_line(&mut self, address: u16, file: String, line: u32) {
+        self.lines.insert(address, SourceLine { file, line });
+    }
+    /// finds the addresses of the instructions on a line, where the file is matched with `same_file`
+    pub fn addresses_of_line(&self, line: u32, same_file: impl Fn(&str) -> bool) -> Vec<u16> {
+        let mut addresses = self.lines.iter()
+            .filter(|(_, source_line)| source_line.line == line && same_file(&source_line.file))
+            .map(|(address, _)| *address)
+            .collect::<Vec<u16>>();
+        addresses.sort();
+        addresses
+    }
     pub fn add_define_use(&mut self, define_use: asm_parser::DefineUse, define: asm_parser::Define) {
         self.symbol_uses.insert(
             define_use.index,
@@ -85,10 +141,33 @@ pub struct Symbol {
     pub symbol_type: SymbolType
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct SourceLine {
+    // relative to the directory of the main file, which is also where the symbol table is written
+    pub file: String,
+    pub line: u32,
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
 pub enum SymbolType {
     Label,
     Pointer,
     Define,
     Subroutine,
-}
\ No newline at end of file
+}
+
+fn strip_folders(name: &str) -> &str {
+    name.rsplit_once('/').unwrap_or(("", name)).1
+}
+
+fn strip_module(name: &str) -> &str {
+    name.rsplit_once('.').unwrap_or(("", name)).1
+}
+
+fn define_address(define: &Symbol) -> Option<u16> {
+    let value = define.value.trim();
+    if !value.starts_with('%') {
+        return None;
+    }
+    Some(asm_parser::Address::from_str(value).address.to_decimal())
+}
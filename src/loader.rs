@@ -3,8 +3,7 @@ use std::time::Duration;
 use serialport::{SerialPortType};
 
 fn parse(data: &[u8]) -> Vec<u8> {
-    let mut result: Vec<u8> = Vec::new();
-    result = data.to_vec();
+    let result: Vec<u8> = data.to_vec();
 
     //todo!()
     result
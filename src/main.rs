@@ -6,8 +6,11 @@ mod simulator;
 mod disassembler;
 mod loader;
 
-use clap::{arg, Command, Arg, value_parser};
+use clap::{arg, Command, Arg, ArgAction, value_parser};
+use crossterm::style::Stylize;
 use crate::disassembler::symbols::SymbolTable;
+use crate::simulator::devices::bridge::BridgeConfig;
+use crate::simulator::devices::serial::FlowControl;
 
 fn main() {
     let matches = Command::new("GoldASM Assembler")
@@ -30,6 +33,58 @@ fn main() {
                 .arg_required_else_help(true)
                 .arg(Arg::new("sourceFile").required(true))
                 .arg(Arg::new("symbolTable").required(false))
+                .arg(arg!(--"serial-pty" "Expose the serial port as a pseudo-terminal (linux only)").conflicts_with("serial-tcp"))
+                .arg(arg!(--"serial-tcp" [port] "Expose the serial port as a TCP server on localhost").value_parser(value_parser!(u16)))
+                .arg(arg!(--"serial-flow" [mode] "How the serial port uses the busy/block flags").value_parser(["busy", "off"]).default_value("busy"))
+                .arg(arg!(--"serial-in" [file] "Feed the contents of a file into the serial port"))
+                .arg(arg!(--"serial-in-delay" [cycles] "Minimum number of cycles between bytes from --serial-in").value_parser(value_parser!(u64)).default_value("0"))
+                .arg(arg!(--"serial-log" [file] "Record all serial traffic to a file"))
+                .arg(arg!(--"serial-cycles-per-byte" [cycles] "How many clock cycles a serial byte takes to send or receive (0 is instant)")
+                    .value_parser(value_parser!(u64)).default_value("0"))
+                .arg(arg!(--"gpio-script" [file] "Play back switch and button events from a file (see doc/Simulator.md)"))
+                .arg(arg!(--"gpio-log" [file] "Record every change to the leds to a file"))
+                .arg(arg!(--"rng-seed" [seed] "Make the random number generator repeatable, starting from this seed (0-65535)")
+                    .value_parser(value_parser!(u16)))
+                .arg(arg!(--framebuffer "Map a 40x25 text framebuffer at E000-E7FF (V shows it in the TUI)"))
+                .arg(arg!(--"stack-fault" [policy] "What to do when the stack overflows or underflows")
+                    .value_parser(["halt", "wrap", "reset"]).default_value("halt"))
+                .arg(arg!(--"invalid-opcode" [policy] "What to do at an opcode that isn't in the ISA (the ISA says reset)")
+                    .value_parser(["reset", "trap"]).default_value("reset"))
+                .arg(arg!(--"cycle-table" [file] "File with the number of cycles each opcode takes"))
+                .arg(arg!(--headless "Run without the TUI, printing serial output to stdout"))
+                .arg(arg!(--"max-cycles" [cycles] "Stop a headless run after this many cycles").value_parser(value_parser!(u64)).requires("headless"))
+                .arg(arg!(--trace [file] "Write a line to the file for every instruction that is run"))
+                .arg(arg!(--"trace-range" [range] "Only trace instructions in this address range (like 0200-02FF)")
+                    .action(ArgAction::Append).requires("trace"))
+                .arg(arg!(--"trace-sub" [name] "Only trace instructions in this subroutine (needs the symbol table)")
+                    .action(ArgAction::Append).requires("trace"))
+                .arg(arg!(--profile [file] "Write a report of where the program spends its time to the file"))
+                .arg(arg!(--"profile-folded" [file] "Write folded stacks (for flamegraph tools) to the file"))
+                .arg(arg!(--coverage "Print which source lines and branches were run when the simulator exits (needs the symbol table)"))
+                .arg(arg!(--lcov [file] "Also write the coverage as an lcov report to the file").requires("coverage"))
+                .arg(arg!(--gdb [port] "Run a gdb remote server on localhost instead of the TUI").value_parser(value_parser!(u16))
+                    .num_args(0..=1).default_missing_value("1234").conflicts_with("headless"))
+        )
+        .subcommand(
+            Command::new("benchmark")
+                .about("Run the given binary as fast as possible and report how fast the simulator is")
+                .arg_required_else_help(true)
+                .arg(Arg::new("sourceFile").required(true))
+                .arg(arg!(--instructions [count] "Stop after this many instructions if the program hasn't finished")
+                    .value_parser(value_parser!(u64)).default_value("100000000"))
+                .arg(arg!(--"no-decode-cache" "Decode every instruction every time it runs (to compare against the cache)"))
+                .arg(arg!(--"rng-seed" [seed] "Make the random number generator repeatable, starting from this seed (0-65535)")
+                    .value_parser(value_parser!(u16)))
+        )
+        .subcommand(
+            Command::new("test")
+                .about("Run the tests in the given test files (.toml), or the .test blocks in the given source files (.gasm) (see doc/Testing.md)")
+                .arg_required_else_help(true)
+                .arg(Arg::new("testFiles").required(true).num_args(1..))
+        )
+        .subcommand(
+            Command::new("dap")
+                .about("Run a debug adapter (Debug Adapter Protocol) over stdio, for debugging from an editor")
         )
         .subcommand(
             Command::new("load")
@@ -45,28 +100,11 @@ fn main() {
             let output_file = sub_matches.get_one::<String>("output").unwrap();
             let output_size = sub_matches.get_one::<u16>("size").unwrap();
 
-            let directory;
-            let filename;
+            let (directory, filename) = assembler::split_path(target_file);
+            let separator = if directory.ends_with('\\') { '\\' } else { '/' };
+            println!("INFO: Assembling file \"{filename}\" in directory \"{}\"", directory.strip_suffix(separator).unwrap_or(&directory));
 
-            if cfg!(target_os = "linux") || !target_file.contains('\\') {
-                // linux or windows with forward slashes
-                directory = target_file.rsplitn(2, '/').nth(1).unwrap_or(".").to_string() + "/";
-                filename = target_file.rsplitn(2, '/').next().unwrap();
-                println!("INFO: Assembling file \"{filename}\" in directory \"{}\"", directory.strip_suffix('/').unwrap_or(&directory));
-            } else if cfg!(target_os = "windows") {
-                // windows with backslashes
-                directory = target_file.rsplitn(2, '\\').nth(1).unwrap_or(".").to_string() + "\\";
-                filename = target_file.rsplitn(2, '\\').next().unwrap();
-                println!("INFO: Assembling file \"{filename}\" in directory \"{}\"", directory.strip_suffix('\\').unwrap_or(&directory));
-            } else {
-                panic!("Only linux and windows are supported!");
-            }
-            
-            let parsed_values = asm_parser::parse(&directory, filename, SymbolTable::new());
-            
-            let instructions = asm_parser::postprocess(parsed_values.0, parsed_values.2, parsed_values.1);
-            
-            let (binary_instructions, symbol_table) = assembler::assemble(instructions.0, *output_size, instructions.1);
+            let (binary_instructions, symbol_table) = assembler::assemble_file(&directory, &filename, *output_size);
             
             assembler::write(&binary_instructions, &directory, &(output_file.to_string() + ".bin"));
             assembler::write(&symbol_table.to_bytes(), &directory, &(output_file.to_string() + ".symbols"));
@@ -76,14 +114,134 @@ fn main() {
             let target_file = sub_matches.get_one::<String>("sourceFile").unwrap();
             let symbol_table_file = sub_matches.get_one::<String>("symbolTable");
 
+            let mut options = simulator::Options::default();
+            if sub_matches.get_flag("serial-pty") {
+                options.serial_bridge = Some(BridgeConfig::Pty);
+            } else if let Some(port) = sub_matches.get_one::<u16>("serial-tcp") {
+                options.serial_bridge = Some(BridgeConfig::Tcp(*port));
+            }
+            if sub_matches.get_one::<String>("serial-flow").unwrap() == "off" {
+                options.serial_flow_control = FlowControl::Off;
+            }
+            options.serial_input = sub_matches.get_one::<String>("serial-in").cloned();
+            options.serial_input_delay = *sub_matches.get_one::<u64>("serial-in-delay").unwrap();
+            options.serial_log = sub_matches.get_one::<String>("serial-log").cloned();
+            options.serial_cycles_per_byte = *sub_matches.get_one::<u64>("serial-cycles-per-byte").unwrap();
+            options.gpio_script = sub_matches.get_one::<String>("gpio-script").cloned();
+            options.gpio_log = sub_matches.get_one::<String>("gpio-log").cloned();
+            options.rng_seed = sub_matches.get_one::<u16>("rng-seed").copied();
+            options.framebuffer = sub_matches.get_flag("framebuffer");
+            options.cycle_table = sub_matches.get_one::<String>("cycle-table").cloned();
+            options.stack_policy = simulator::fault::StackPolicy::parse(sub_matches.get_one::<String>("stack-fault").unwrap())
+                .expect("clap only allows the listed policies");
+            options.opcode_policy = simulator::fault::OpcodePolicy::parse(sub_matches.get_one::<String>("invalid-opcode").unwrap())
+                .expect("clap only allows the listed policies");
+            if let Some(path) = sub_matches.get_one::<String>("trace") {
+                let ranges = sub_matches.get_many::<String>("trace-range").unwrap_or_default()
+                    .map(|range| simulator::trace::parse_range(range).unwrap_or_else(|error| panic!("{error}!")))
+                    .collect();
+                let subroutines = sub_matches.get_many::<String>("trace-sub").unwrap_or_default().cloned().collect();
+                options.trace = Some(simulator::trace::TraceOptions { path: path.clone(), ranges, subroutines });
+            }
+            let profile_report = sub_matches.get_one::<String>("profile").cloned();
+            let profile_folded = sub_matches.get_one::<String>("profile-folded").cloned();
+            if profile_report.is_some() || profile_folded.is_some() {
+                options.profile = Some(simulator::profiler::ProfileOptions { report: profile_report, folded: profile_folded });
+            }
+            if sub_matches.get_flag("coverage") {
+                // source files in the symbol table are relative to the directory it was written to
+                let source_directory = symbol_table_file.and_then(|path| std::path::Path::new(path).parent())
+                    .map(|directory| directory.to_path_buf()).unwrap_or_default();
+                let lcov = sub_matches.get_one::<String>("lcov").cloned();
+                options.coverage = Some(simulator::coverage::CoverageOptions { lcov, source_directory });
+            }
+
+            let load_symbol_table = || symbol_table_file.map(|path| {
+                SymbolTable::from_bytes(&std::fs::read(path).unwrap_or_else(|_| panic!("Symbol table not found ({path})!")))
+            }).unwrap_or_default();
+            if let Some(port) = sub_matches.get_one::<u16>("gdb") {
+                eprintln!("Debugging binary file {target_file} with gdb");
+                simulator::gdb::serve(target_file, load_symbol_table(), options, *port);
+                return;
+            }
+            if sub_matches.get_flag("headless") {
+                // stdout is only the program's serial output, so it can be piped or diffed
+                eprintln!("Simulating binary file {target_file} headless");
+                let max_cycles = sub_matches.get_one::<u64>("max-cycles").copied();
+                let symbol_table = load_symbol_table();
+                let (stop_reason, cycles) = simulator::headless::run(target_file, &symbol_table, options, max_cycles);
+                match stop_reason {
+                    simulator::headless::StopReason::EndLoop => eprintln!("INFO: Program finished after {cycles} cycles"),
+                    simulator::headless::StopReason::CycleLimit => {
+                        eprintln!("{}", format!("WARNING: Stopped at the cycle limit ({cycles} cycles) before the program finished").yellow());
+                        std::process::exit(1);
+                    }
+                    simulator::headless::StopReason::Fault(fault) => {
+                        eprintln!("{}", format!("ERROR: Program stopped after {cycles} cycles: {}", fault.report(&symbol_table)).red().bold());
+                        std::process::exit(1);
+                    }
+                }
+                return;
+            }
+
             if let Some(symbol_table_file) = symbol_table_file {
                 println!("Simulating binary file {target_file} with symbol table {symbol_table_file}");
-                simulator::run_with_symbol_table(target_file.clone(), symbol_table_file.clone()).unwrap();
+                simulator::run_with_symbol_table(target_file.clone(), symbol_table_file.clone(), options).unwrap();
             } else {
                 println!("Simulating binary file {target_file}");
-                simulator::run(target_file.clone()).unwrap();
+                simulator::run(target_file.clone(), options).unwrap();
             }
         }
+        Some(("benchmark", sub_matches)) => {
+            let target_file = sub_matches.get_one::<String>("sourceFile").unwrap();
+            let max_instructions = *sub_matches.get_one::<u64>("instructions").unwrap();
+            let decode_cache = !sub_matches.get_flag("no-decode-cache");
+            let options = simulator::Options {
+                rng_seed: sub_matches.get_one::<u16>("rng-seed").copied(),
+                ..simulator::Options::default()
+            };
+            let result = simulator::benchmark::run(target_file, options, max_instructions, decode_cache);
+            if !result.finished {
+                eprintln!("{}", format!("WARNING: Stopped after {} instructions before the program finished", result.instructions).yellow());
+            }
+            println!("Ran {} instructions ({} cycles) in {:.3} s", result.instructions, result.cycles, result.elapsed.as_secs_f64());
+            println!("{:.2} MIPS ({:.2} MHz simulated)", result.mips(), result.simulated_mhz());
+            if decode_cache {
+                let lookups = (result.cache_hits + result.cache_misses).max(1);
+                println!("Decode cache: {} hits, {} misses ({:.1}% hit rate)",
+                         result.cache_hits, result.cache_misses, result.cache_hits as f64 * 100.0 / lookups as f64);
+            }
+        }
+        Some(("test", sub_matches)) => {
+            let (mut passed, mut failed) = (0, 0);
+            for path in sub_matches.get_many::<String>("testFiles").unwrap() {
+                println!("INFO: Running tests in {path}");
+                let results = if path.ends_with(".gasm") {
+                    simulator::harness::run_source_file(path)
+                } else {
+                    simulator::harness::run_file(path)
+                };
+                for result in results {
+                    if result.passed() {
+                        passed += 1;
+                        println!("{} {} ({} cycles)", "PASS".green().bold(), result.name, result.cycles);
+                    } else {
+                        failed += 1;
+                        println!("{} {}", "FAIL".red().bold(), result.name);
+                        for failure in result.failures {
+                            println!("    {failure}");
+                        }
+                    }
+                }
+            }
+            println!("{passed} passed, {failed} failed");
+            if failed > 0 {
+                std::process::exit(1);
+            }
+        }
+        Some(("dap", _)) => {
+            simulator::dap::serve();
+        }
         Some(("load", sub_matches)) => {
             let filename = sub_matches.get_one::<String>("file").unwrap();
             //let file_bytes = std::fs::read(filename).unwrap_or_else(|_| panic!("File not found ({filename})!"));
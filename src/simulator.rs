@@ -1,9 +1,33 @@
+mod auto_run;
+pub mod benchmark;
 pub mod bin_parser;
+pub mod bus;
+mod call_stack;
+mod call_stack_panel;
+pub mod coverage;
+mod decode_cache;
+pub mod dap;
+pub mod devices;
+mod disassembly_view;
+mod display_panel;
 mod executor;
+pub mod fault;
+mod framebuffer_view;
+pub mod gdb;
+mod gpio_panel;
+pub mod harness;
+pub mod headless;
+pub mod instruments;
+mod memory_view;
+pub mod profiler;
+pub mod timing;
+pub mod trace;
+pub mod watch;
+mod watch_panel;
 
 use std::collections::VecDeque;
 use std::io;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use crossterm::event::{self, Event, KeyCode, KeyEvent};
 use ratatui::prelude::*;
 use ratatui::{
@@ -21,32 +45,128 @@ use ratatui::{
     DefaultTerminal, Frame,
 };
 use ratatui::layout::Flex;
+use crate::simulator::auto_run::Pacer;
 use crate::simulator::executor::Processor;
-use crate::disassembler;
-use crate::disassembler::symbols::{SymbolTable, SymbolType};
-use crate::simulator::bin_parser::Instruction;
-
-#[derive(Debug, Default, Clone)]
+use crate::disassembler::symbols::SymbolTable;
+use crate::simulator::disassembly_view::DisassemblyView;
+use crate::simulator::gpio_panel::GpioPanel;
+use crate::simulator::watch_panel::WatchPanel;
+use crate::simulator::memory_view::{MemoryView, Prompt, PromptKind};
+use crate::simulator::coverage::CoverageOptions;
+use crate::simulator::fault::{Fault, OpcodePolicy, StackPolicy};
+use crate::simulator::instruments::Instruments;
+use crate::simulator::profiler::ProfileOptions;
+use crate::simulator::trace::TraceOptions;
+use std::cell::RefCell;
+use std::rc::Rc;
+use crate::simulator::devices::DeviceOptions;
+use crate::simulator::devices::bridge::BridgeConfig;
+use crate::simulator::devices::gpio;
+use crate::simulator::devices::gpio::{Gpio, GpioLog, GpioOptions};
+use crate::simulator::devices::framebuffer;
+use crate::simulator::devices::framebuffer::Framebuffer;
+use crate::simulator::devices::lcd::Lcd;
+use crate::simulator::devices::seven_segment::SevenSegment;
+use crate::simulator::devices::serial::{FlowControl, SerialLog, SerialOptions, SerialPort};
+
+#[derive(Debug, Default)]
 pub struct App {
     cpu: Processor,
     exit: bool,
     binary_path: String,
     symbol_table: SymbolTable, // just empty if none
-    instruction_state: ListState,
+    disassembly_view: DisassemblyView,
     stack_state: ListState,
     auto_run: bool,
+    pacer: Pacer,
     serial_text: Vec<char>,
     send_mode: bool,
     serial_tx_buffer: VecDeque<char>,
+    memory_view: MemoryView,
+    gpio_panel: GpioPanel,
+    watch_panel: WatchPanel,
+    /// showing the framebuffer over everything else
+    framebuffer_view: bool,
+    prompt: Option<Prompt>,
+    device_options: DeviceOptions,
+    options: Options,
+    instruments: Instruments,
+    /// the last fault since the cpu was reset, shown until the next reset
+    last_fault: Option<Fault>,
 }
+
+/// options for the simulator that come from the command line
+#[derive(Debug, Default, Clone)]
+pub struct Options {
+    pub serial_bridge: Option<BridgeConfig>,
+    pub serial_flow_control: FlowControl,
+    /// file to feed into the serial port
+    pub serial_input: Option<String>,
+    pub serial_input_delay: u64,
+    /// file to record serial traffic to
+    pub serial_log: Option<String>,
+    /// file with switch and button events to play back
+    pub gpio_script: Option<String>,
+    /// file to record the leds to
+    pub gpio_log: Option<String>,
+    pub framebuffer: bool,
+    pub rng_seed: Option<u16>,
+    pub trace: Option<TraceOptions>,
+    pub profile: Option<ProfileOptions>,
+    pub coverage: Option<CoverageOptions>,
+    /// file to read the cycle table from, the default estimate is used if there isn't one
+    pub cycle_table: Option<String>,
+    pub serial_cycles_per_byte: u64,
+    pub stack_policy: StackPolicy,
+    pub opcode_policy: OpcodePolicy,
+}
+impl Options {
+    /// opens anything the devices need from the host
+    fn open_devices(&self) -> DeviceOptions {
+        let bridge = self.serial_bridge.map(|config| -> devices::serial::SharedBridge {
+            let bridge = config.open();
+            eprintln!("INFO: Serial port is available at {}", bridge.description());
+            Rc::new(RefCell::new(bridge))
+        });
+        let scripted_input = self.serial_input.as_ref().map(|path| {
+            std::fs::read(path).unwrap_or_else(|_| panic!("Serial input file not found ({path})!"))
+        }).unwrap_or_default();
+        let log = self.serial_log.as_ref().map(|path| Rc::new(RefCell::new(SerialLog::create(path))));
+        let gpio_script = self.gpio_script.as_ref().map(|path| {
+            let text = std::fs::read_to_string(path).unwrap_or_else(|_| panic!("GPIO script not found ({path})!"));
+            gpio::parse_script(&text).unwrap_or_else(|error| panic!("{error}!"))
+        }).unwrap_or_default();
+        DeviceOptions {
+            serial: SerialOptions {
+                flow_control: self.serial_flow_control,
+                bridge,
+                scripted_input,
+                scripted_input_delay: self.serial_input_delay,
+                cycles_per_byte: self.serial_cycles_per_byte,
+                log,
+            },
+            gpio: GpioOptions {
+                script: gpio_script,
+                log: self.gpio_log.as_ref().map(|path| Rc::new(RefCell::new(GpioLog::create(path)))),
+            },
+            rng_seed: self.rng_seed,
+            framebuffer: self.framebuffer,
+            timing: self.cycle_table.as_ref().map(|path| timing::CycleTable::load(path)).unwrap_or_default(),
+            stack_policy: self.stack_policy,
+            opcode_policy: self.opcode_policy,
+        }
+    }
+}
+
 impl App {
 
     /// runs the application's main loop until the user quits
-    pub fn run(&mut self, terminal: &mut DefaultTerminal, binary_path: String) -> io::Result<()> {
+    pub fn run(&mut self, terminal: &mut DefaultTerminal, binary_path: String, device_options: DeviceOptions) -> io::Result<()> {
         self.binary_path = binary_path;
+        self.device_options = device_options;
         self.symbol_table = SymbolTable::new();
+        self.instruments = Instruments::new(&self.options, &self.symbol_table);
         self.reset();
-        self.instruction_state = ListState::default();
         self.stack_state = ListState::default();
         while !self.exit {
             terminal.draw(|frame| self.draw(frame))?;
@@ -54,15 +174,16 @@ impl App {
         }
         Ok(())
     }
-    pub fn run_with_symbol_table(&mut self, terminal: &mut DefaultTerminal, binary_path: String, table_path: String) -> io::Result<()> {
+    pub fn run_with_symbol_table(&mut self, terminal: &mut DefaultTerminal, binary_path: String, table_path: String, device_options: DeviceOptions) -> io::Result<()> {
         self.binary_path = binary_path;
+        self.device_options = device_options;
 
         let symbol_table_file = std::fs::read(&table_path).unwrap_or_else(|_| panic!("Symbol table not found ({table_path})!"));
         self.symbol_table = SymbolTable::from_bytes(&symbol_table_file);
+        self.instruments = Instruments::new(&self.options, &self.symbol_table);
 
         self.reset();
 
-        self.instruction_state = ListState::default();
         self.stack_state = ListState::default();
 
         while !self.exit {
@@ -73,42 +194,76 @@ impl App {
     }
 
     fn draw(&mut self, frame: &mut Frame) {
-        // if auto run, then execute a step
-        if self.auto_run {
-            self.step();
-        }
-        
         // update memory lists
-        *self.instruction_state.offset_mut() = 16 - 5;
-        self.instruction_state.select(Some(16));
-
         *self.stack_state.offset_mut() = (self.cpu.stack_pointer as usize).saturating_sub(5);
         self.stack_state.select(Some(self.cpu.stack_pointer as usize));
         
         // create outer box
         let title = Line::from(" GoldCore Simulator ".bold());
-        let tui_instructions = Line::from(vec![
+        let mut tui_instructions = vec![
             " Start Auto Run ".into(),
             "<A>".blue().bold(),
             " Stop Auto Run ".into(),
             "<P>".blue().bold(),
+            " Speed ".into(),
+            "<-/+>".blue().bold(),
             " Edit tx buffer ".into(),
             "<S>".blue().bold(),
+            " Memory ".into(),
+            "<M>".blue().bold(),
+            " GPIO ".into(),
+            "<O>".blue().bold(),
+            " Watches ".into(),
+            "<E>".blue().bold(),
+            " Goto ".into(),
+            "<G>".blue().bold(),
+            " Write ".into(),
+            "<W>".blue().bold(),
+            " Disassemble at ".into(),
+            "<D>".blue().bold(),
+            " Scroll ".into(),
+            "<PgUp/PgDn/Home>".blue().bold(),
+            " IRQ ".into(),
+            "<I>".blue().bold(),
+            " NMI ".into(),
+            "<N>".blue().bold(),
             " Reset ".into(),
             "<Space>".blue().bold(),
             " Step ".into(),
             "<Right>".blue().bold(),
             " Quit ".into(),
             "<Escape> ".blue().bold(),
-        ]);
+        ];
+        let framebuffer = self.cpu.bus.device::<Framebuffer>();
+        if framebuffer.is_some() {
+            tui_instructions.splice(14..14, [" Framebuffer ".into(), "<V>".blue().bold()]);
+        }
         let outer_block = Block::bordered()
             .title(title.centered())
-            .title_bottom(tui_instructions.centered())
+            .title_bottom(Line::from(tui_instructions).centered())
             .border_set(border::THICK);
+
+        // ------------------------------ FRAMEBUFFER ------------------------------
+        if self.framebuffer_view && let Some(framebuffer) = framebuffer {
+            let vertical = Layout::vertical([Constraint::Length(framebuffer::ROWS as u16 + 2)]).flex(Flex::Center);
+            let horizontal = Layout::horizontal([Constraint::Length(framebuffer::COLUMNS as u16 + 2)]).flex(Flex::Center);
+            let [screen] = vertical.areas(outer_block.inner(frame.area()));
+            let [screen] = horizontal.areas(screen);
+            frame.render_widget(outer_block, frame.area());
+            frame.render_widget(framebuffer_view::widget(framebuffer), screen);
+            return;
+        }
+        // ------------------------------ END FRAMEBUFFER ------------------------------
         
         // make layout of stuff
+        let [top_area, memory_area] =
+            Layout::vertical([Constraint::Percentage(65), Constraint::Percentage(35)]).areas(outer_block.inner(frame.area()));
         let [status_area, io_area, instruction_area, stack_area] =
-            Layout::horizontal([Constraint::Percentage(25), Constraint::Percentage(25), Constraint::Percentage(40), Constraint::Percentage(10)]).areas(outer_block.inner(frame.area()));
+            Layout::horizontal([Constraint::Percentage(25), Constraint::Percentage(25), Constraint::Percentage(40), Constraint::Percentage(10)]).areas(top_area);
+        let [cpu_area, watch_area, call_stack_area] =
+            Layout::vertical([Constraint::Min(12), Constraint::Percentage(25), Constraint::Percentage(25)]).areas(status_area);
+        let [serial_area, gpio_area, display_area] =
+            Layout::vertical([Constraint::Min(3), Constraint::Length(6), Constraint::Length(8)]).areas(io_area);
 
         // ------------------------------ CPU STATE ------------------------------
         let title = Line::from(" CPU State ");
@@ -134,8 +289,11 @@ impl App {
         if self.cpu.status_register & 0b000001_00 > 1 {
             push_to_string(&mut status_register_text, "negative");
         }
+        if self.cpu.status_register & executor::INTERRUPT_ENABLE > 1 {
+            push_to_string(&mut status_register_text, "interrupts enabled");
+        }
 
-        let status_text = Text::from(vec![Line::from(vec![
+        let mut status_text = Text::from(vec![Line::from(vec![
             "Accumulator: ".into(),
             format!("{:02x} ", self.cpu.accumulator).to_string().yellow(),]), Line::from(vec![
             "Registers: ".into(),
@@ -149,143 +307,25 @@ impl App {
             "Program counter: ".into(),
             format!("{:04x} ", self.cpu.program_counter).to_string().yellow(),]), Line::from(vec![
             "Stack pointer: ".into(),
-            format!("{:02x} ", self.cpu.stack_pointer).to_string().yellow(),]),
+            format!("{:02x} ", self.cpu.stack_pointer).to_string().yellow(),]), Line::from(vec![
+            "Cycles: ".into(),
+            format!("{} ({} instructions) ", self.cpu.cycles, self.cpu.instructions).to_string().yellow(),]), Line::from(vec![
+            "Auto run: ".into(),
+            format!("{}{} ", self.pacer.describe(), if self.auto_run { "" } else { ", stopped" }).yellow(),]),
         ]);
+        if let Some(fault) = &self.last_fault {
+            status_text.push_line(Line::from(format!("Fault: {}", fault.describe()).red().bold()));
+            for call in fault.call_chain(&self.symbol_table) {
+                status_text.push_line(Line::from(format!("  {call}").light_red()));
+            }
+        }
 
         let cpu_state = Paragraph::new(status_text)
             .block(block);
         // ------------------------------ END CPU STATE ------------------------------
         
-        // instructions list
-        let memory_list_start = self.cpu.program_counter.saturating_sub(16) as usize;
-        let memory_list_end = self.cpu.program_counter.saturating_add(48) as usize;
-
-        let instructions = self.cpu.memory[memory_list_start..=memory_list_end].to_vec();
-
-        // ------------------------------ LIVE DISASSEMBLY ------------------------------
-        // create variables
-        let mut parsed_instructions: Vec<Instruction> = Vec::with_capacity(instructions.len());
-        let mut bytes_to_skip: Vec<u8> = Vec::with_capacity(instructions.len());
-        let mut program_counter_value: u32 = 0x0200;
-        let mut disassemble_start: u32 = 0;
-        let mut disassemble_end: u32 = 0;
-        // parse instructions
-        while program_counter_value < 0xFF00.max(memory_list_end as u32) {
-            // parse the instruction
-            let instruction = bin_parser::parse_instruction(&self.cpu.memory, program_counter_value as u16);
-            if let Ok(instruction) = instruction {
-                let (parsed_instruction, num_extra_bytes) = instruction;
-
-                // if we're at an index after memory_list_start and before memory_list_end, add it to the list
-                if program_counter_value >= memory_list_start as u32 && program_counter_value <= memory_list_end as u32 {
-                    if disassemble_start == 0 {
-                        disassemble_start = program_counter_value;
-                    }
-                    parsed_instructions.push(parsed_instruction);
-                    bytes_to_skip.push(num_extra_bytes);
-                    disassemble_end = program_counter_value;
-                }
-
-                // increment the program counter
-                program_counter_value += num_extra_bytes as u32;
-                program_counter_value += 1;
-            } else {
-                program_counter_value += 1;
-            }
-        }
-        if disassemble_start < memory_list_start as u32 {
-            disassemble_start = memory_list_start as u32;
-        }
-        if disassemble_end > memory_list_end as u32 {
-            disassemble_end = memory_list_end as u32;
-        }
-
-        let mut disassembled_lines = disassembler::disassemble(parsed_instructions, bytes_to_skip);
-
-        for _ in 0..(disassemble_start as usize - memory_list_start) {
-            disassembled_lines.insert(0, "".to_string());
-        }
-        for _ in 0..(instructions.len() - (disassemble_end - disassemble_start) as usize) {
-            disassembled_lines.push("".to_string());
-        }
-
-        let memory_strings: Vec<Line> = instructions.iter().enumerate().map(|(index, item)| -> Line {
-            let disassembled_line = disassembled_lines[index].as_str();
-            let memory_index = index + memory_list_start;
-
-            let mut final_line = format!("0x{:04x?}: ", index + memory_list_start).yellow() + format!("0x{item:02x?}").green();
-
-            if !disassembled_line.is_empty() {
-                final_line = final_line + " -> ".light_green() + disassembled_line.light_green();
-            }
-
-            // ------------------------------ SYMBOL TABLE ------------------------------
-            if let Some(symbol) = self.symbol_table.symbol_uses.get(&(memory_index as u16)) {
-                let mut symbol = symbol.clone();
-                // remove folder names
-                symbol.name = symbol.name.rsplit_once('/').unwrap_or(("", &symbol.name)).1.to_string();
-                // convert value to hex
-                if let Ok(symbol_value) = symbol.value.parse::<u16>() {
-                    symbol.value = format!("{symbol_value:04x}");
-                }
-                // add prefix based on type
-                match symbol.symbol_type {
-                    SymbolType::Pointer => {
-                        symbol.name = "*".to_string() + &symbol.name;
-                        // if you see an into iter on final_line, it's magic trickery so I can keep
-                        // the styling while still doing operations on the string
-                        let final_line_vec: Vec<Span> = final_line.into_iter().map(|span| {
-                            span.clone().content(span.content.replace(&symbol.value, ""))
-                        }).collect();
-                        final_line = Line::from(final_line_vec);
-                    }
-                    SymbolType::Label => {
-                        symbol.name = "~".to_string() + &symbol.name;
-                        let final_line_vec: Vec<Span> = final_line.into_iter().map(|span| {
-                            span.clone().content(span.content.replace(&("%".to_string() + &symbol.value), ""))
-                        }).collect();
-                        final_line = Line::from(final_line_vec);
-                        symbol.value = "%".to_string() + &symbol.value;
-                    }
-                    SymbolType::Subroutine => {
-                        symbol.name = "~".to_string() + symbol.name.strip_suffix("_SR").expect("Somehow had a subroutine ending in the wrong suffix? Report this to me with your source files.");
-                        let final_line_vec: Vec<Span> = final_line.into_iter().map(|span| {
-                            span.clone().content(span.content.replace(&("%".to_string() + &symbol.value), ""))
-                        }).collect();
-                        final_line = Line::from(final_line_vec);
-                        symbol.value = "%".to_string() + &symbol.value;
-                    }
-                    _ => eprintln!("{symbol:?}")
-                }
-
-                final_line += format!("{}: {}", symbol.name, symbol.value).light_blue()
-            }
-            if let Some(symbol) = self.symbol_table.symbols.get(&(memory_index as u16)) {
-                if symbol.name.ends_with("_EndSR") {
-                    final_line += format!(" {}", symbol.clone().name.strip_suffix("_EndSR").unwrap()).light_blue()
-                } else if symbol.name.ends_with("_SR") {
-                    let mut final_line_vec: Vec<Span> = final_line.into_iter().collect::<Vec<Span>>();
-                    final_line_vec.insert(0, format!("sr {}: ", symbol.clone().name.strip_suffix("_SR").unwrap()).blue());
-                    final_line = Line::from(final_line_vec);
-                } else {
-                    let mut final_line_vec: Vec<Span> = final_line.into_iter().collect::<Vec<Span>>();
-                    final_line_vec.insert(0, format!("{}: ", symbol.clone().name).blue());
-                    final_line = Line::from(final_line_vec);
-                }
-            }
-            // ------------------------------ END SYMBOL TABLE ------------------------------
-            final_line
-        }).collect();
-        // ------------------------------ END LIVE DISASSEMBLY ------------------------------
-        
-        let memory_list = List::new(memory_strings)
-            .block(Block::bordered().title(" Memory "))
-            .highlight_symbol("-> ")
-            .scroll_padding(32)
-            .repeat_highlight_symbol(false);
-        
         // stack list
-        let stack = self.cpu.memory[0x0100..0x0200].to_vec();
+        let stack = self.cpu.bus.memory[0x0100..0x0200].to_vec();
         let stack_strings: Vec<Line> = stack.iter().enumerate().map(|(index, item)| -> Line {
             format!("0x{:04x?}: ", 0x0100 + index).yellow() + format!("0x{item:02x?}").green()
         }).collect();
@@ -297,8 +337,11 @@ impl App {
 
         // ------------------------------ IO BLOCK ------------------------------
         let serial_text = self.serial_text.iter().collect::<String>();
-        let io_block = Block::bordered()
+        let mut io_block = Block::bordered()
             .title(" I/O ");
+        if let Some(bridge) = &self.device_options.serial.bridge {
+            io_block = io_block.title_bottom(Line::from(format!(" {} ", bridge.borrow().description())).right_aligned());
+        }
 
         let io_text_lines = serial_text.lines().map(|line| -> Line {
             Line::from(line.white())
@@ -308,12 +351,28 @@ impl App {
             .wrap(Wrap { trim: false });
         // ------------------------------ END IO BLOCK ------------------------------
         
+        let gpio = self.cpu.bus.device::<Gpio>().expect("The gpio should always be on the bus");
+        let gpio_view = self.gpio_panel.widget(gpio);
+        let seven_segment = self.cpu.bus.device::<SevenSegment>().expect("The seven segment display should always be on the bus");
+        let lcd = self.cpu.bus.device::<Lcd>().expect("The lcd should always be on the bus");
+        let display_view = display_panel::widget(seven_segment, lcd);
+
+        let memory_view = self.memory_view.widget(&self.cpu, &self.symbol_table, memory_area.height);
+        let watch_view = self.watch_panel.widget(&self.cpu);
+        let call_stack_view = call_stack_panel::widget(&call_stack::frames(&self.cpu, &self.symbol_table));
+        let disassembly_view = self.disassembly_view.widget(&self.cpu, &self.symbol_table, instruction_area.height);
+
         // render everything
         frame.render_widget(outer_block, frame.area());
-        frame.render_widget(io_text, io_area);
-        frame.render_stateful_widget(memory_list, instruction_area, &mut self.instruction_state);
+        frame.render_widget(memory_view, memory_area);
+        frame.render_widget(io_text, serial_area);
+        frame.render_widget(gpio_view, gpio_area);
+        frame.render_widget(display_view, display_area);
+        frame.render_widget(disassembly_view, instruction_area);
         frame.render_stateful_widget(stack_list, stack_area, &mut self.stack_state);
-        frame.render_widget(cpu_state, status_area);
+        frame.render_widget(cpu_state, cpu_area);
+        frame.render_widget(watch_view, watch_area);
+        frame.render_widget(call_stack_view, call_stack_area);
         if self.send_mode {
             let vertical = Layout::vertical([Constraint::Percentage(75)]).flex(Flex::Center);
             let horizontal = Layout::horizontal([Constraint::Percentage(50)]).flex(Flex::Center);
@@ -332,11 +391,27 @@ impl App {
             frame.render_widget(Clear, popup);
             frame.render_widget(text_to_send, popup);
         }
+        if let Some(prompt) = &self.prompt {
+            let vertical = Layout::vertical([Constraint::Length(3)]).flex(Flex::Center);
+            let horizontal = Layout::horizontal([Constraint::Percentage(60)]).flex(Flex::Center);
+            let [popup] = vertical.areas(frame.area());
+            let [popup] = horizontal.areas(popup);
+
+            let mut block = Block::bordered()
+                .title(Line::from(prompt.title()).centered());
+            if let Some(error) = &prompt.error {
+                block = block.title_bottom(Line::from(format!(" {error} ").red().bold()).centered());
+            }
+            let input = Paragraph::new(Line::from(prompt.input.clone().white()))
+                .block(block)
+                .on_dark_gray();
+            frame.render_widget(Clear, popup);
+            frame.render_widget(input, popup);
+        }
     }
 
     fn handle_events(&mut self) -> io::Result<()> {
-        // 100hz update rate
-        // todo: make the auto run speed adjustable
+        // 100hz update rate (auto run at max speed spends most of the time running instead)
         if event::poll(Duration::from_millis(10))? {
             match event::read()? {
                 // events are registered on press and release
@@ -345,19 +420,86 @@ impl App {
                 }
                 _ => {}
             };
-            Ok(())
-        } else {
-            Ok(())
         }
+        if self.auto_run {
+            self.run_frame();
+        }
+        Ok(())
+    }
+
+    /// runs as many instructions as the auto run speed says are due
+    fn run_frame(&mut self) {
+        let (steps, deadline) = self.pacer.frame(Instant::now());
+        for step in 0..steps {
+            if !self.auto_run {
+                break;
+            }
+            self.step();
+            // checking the time is slow compared to a step, so only every so often
+            if step % 1024 == 1023 && Instant::now() >= deadline {
+                break;
+            }
+        }
+        self.pacer.measure(Instant::now(), self.cpu.instructions);
+    }
+    fn stop_auto_run(&mut self) {
+        self.auto_run = false;
+        self.pacer.stop();
     }
 
     fn handle_key_event(&mut self, key_event: KeyEvent) {
+        if self.prompt.is_some() {
+            self.handle_prompt_key_event(key_event);
+            return;
+        }
+        if self.memory_view.focused {
+            self.handle_memory_key_event(key_event);
+            return;
+        }
+        if self.framebuffer_view {
+            // only the keys for running the program, since nothing else is on screen
+            match key_event.code {
+                KeyCode::Esc | KeyCode::Char('v') => self.framebuffer_view = false,
+                KeyCode::Char(' ') => self.reset(),
+                KeyCode::Right => self.step(),
+                KeyCode::Char('a') => self.auto_run = true,
+                KeyCode::Char('p') => self.stop_auto_run(),
+                KeyCode::Char('+') | KeyCode::Char('=') => self.pacer.faster(),
+                KeyCode::Char('-') => self.pacer.slower(),
+                KeyCode::Char('i') => self.cpu.bus.irq_requested = true,
+                KeyCode::Char('n') => self.cpu.bus.nmi_pending = true,
+                _ => {}
+            }
+            return;
+        }
+        if self.watch_panel.focused {
+            match key_event.code {
+                KeyCode::Enter => self.prompt = Some(Prompt::new(PromptKind::Watch)),
+                code => self.watch_panel.handle_key(code),
+            }
+            return;
+        }
+        if self.gpio_panel.focused {
+            let gpio = self.cpu.bus.device_mut::<Gpio>().expect("The gpio should always be on the bus");
+            self.gpio_panel.handle_key(gpio, key_event.code);
+            return;
+        }
         if !self.send_mode {
             if key_event.code == KeyCode::Char('s') {
                 self.send_mode = true;
                 return;
             }
             match key_event.code {
+                KeyCode::Char('m') => self.memory_view.focused = true,
+                KeyCode::Char('o') => self.gpio_panel.focused = true,
+                KeyCode::Char('e') => self.watch_panel.focused = true,
+                KeyCode::Char('v') => self.framebuffer_view = self.cpu.bus.device::<Framebuffer>().is_some(),
+                KeyCode::Char('g') => self.prompt = Some(Prompt::new(PromptKind::Goto)),
+                KeyCode::Char('w') => self.prompt = Some(Prompt::new(PromptKind::Write)),
+                KeyCode::Char('d') => self.prompt = Some(Prompt::new(PromptKind::Disassembly)),
+                KeyCode::PageUp => self.disassembly_view.scroll(self.cpu.program_counter, -1),
+                KeyCode::PageDown => self.disassembly_view.scroll(self.cpu.program_counter, 1),
+                KeyCode::Home => self.disassembly_view.follow_program_counter(),
                 KeyCode::Char(' ') => self.reset(),
                 KeyCode::Esc => self.exit(),
                 // left arrow
@@ -366,7 +508,11 @@ impl App {
                 KeyCode::Right => self.step(),
                 // up arrow
                 KeyCode::Char('a') => self.auto_run = true,
-                KeyCode::Char('p') => self.auto_run = false,
+                KeyCode::Char('p') => self.stop_auto_run(),
+                KeyCode::Char('+') | KeyCode::Char('=') => self.pacer.faster(),
+                KeyCode::Char('-') => self.pacer.slower(),
+                KeyCode::Char('i') => self.cpu.bus.irq_requested = true,
+                KeyCode::Char('n') => self.cpu.bus.nmi_pending = true,
                 _ => {}
             }
         } else {
@@ -388,34 +534,124 @@ impl App {
         }
     }
 
+    fn handle_memory_key_event(&mut self, key_event: KeyEvent) {
+        match key_event.code {
+            KeyCode::Esc | KeyCode::Char('m') => self.memory_view.focused = false,
+            KeyCode::Char('g') => self.prompt = Some(Prompt::new(PromptKind::Goto)),
+            KeyCode::Char('w') => self.prompt = Some(Prompt::new(PromptKind::Write)),
+            KeyCode::Left => self.memory_view.move_cursor(-1),
+            KeyCode::Right => self.memory_view.move_cursor(1),
+            KeyCode::Up => self.memory_view.move_cursor(-0x10),
+            KeyCode::Down => self.memory_view.move_cursor(0x10),
+            KeyCode::PageUp => self.memory_view.move_cursor(-0x100),
+            KeyCode::PageDown => self.memory_view.move_cursor(0x100),
+            KeyCode::Char(character) if character.is_ascii_hexdigit() => {
+                let digit = character.to_digit(16).unwrap() as u8;
+                if self.memory_view.type_hex_digit(&mut self.cpu, digit) {
+                    self.disassembly_view.record_write(&self.cpu.bus.memory, self.memory_view.cursor.wrapping_sub(1));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_prompt_key_event(&mut self, key_event: KeyEvent) {
+        let Some(prompt) = &mut self.prompt else {
+            return;
+        };
+        match key_event.code {
+            KeyCode::Esc => self.prompt = None,
+            KeyCode::Backspace => {
+                prompt.input.pop();
+            }
+            KeyCode::Char(character) => prompt.input.push(character),
+            KeyCode::Enter => {
+                let result = match prompt.kind {
+                    PromptKind::Goto => memory_view::parse_address(&self.symbol_table, &prompt.input)
+                        .map(|address| {
+                            self.memory_view.goto(address);
+                            self.memory_view.focused = true;
+                        }),
+                    PromptKind::Watch => watch::parse_watch(&self.symbol_table, &prompt.input)
+                        .map(|watch| self.watch_panel.add(watch)),
+                    PromptKind::Disassembly => memory_view::parse_address(&self.symbol_table, &prompt.input)
+                        .map(|address| self.disassembly_view.goto(address)),
+                    PromptKind::Write => memory_view::apply_write(&mut self.cpu, &self.symbol_table, &prompt.input)
+                        .map(|written_address| {
+                            if let Some(address) = written_address {
+                                self.memory_view.record_write(address);
+                                self.disassembly_view.record_write(&self.cpu.bus.memory, address);
+                            }
+                        }),
+                };
+                match result {
+                    Ok(()) => self.prompt = None,
+                    Err(error) => prompt.error = Some(error),
+                }
+            }
+            _ => ()
+        }
+    }
+
     fn exit(&mut self) {
         self.exit = true;
     }
     fn step(&mut self) {
-        self.cpu.step();
-        if self.cpu.memory[0xFF01] > 0 {
-            // new data
-            self.cpu.memory[0xFF01] = 0;
-            self.serial_text.push(self.cpu.memory[0xFF00] as char);
+        self.instruments.step(&mut self.cpu);
+        self.memory_view.record_step(&self.cpu.bus.writes);
+        self.disassembly_view.record_writes(&self.cpu.bus.memory, &self.cpu.bus.writes);
+        if let Some(fault) = self.cpu.fault.clone() {
+            if fault.halted() {
+                self.stop_auto_run();
+            }
+            self.last_fault = Some(fault);
+        }
+        let serial = self.cpu.bus.device_mut::<SerialPort>().expect("The serial port should always be on the bus");
+        for byte in serial.take_output() {
+            self.serial_text.push(byte as char);
         }
-        if self.cpu.memory[0xFF09] == 0 && self.cpu.memory[0xFF0A] == 0 && !self.send_mode {
-            // clear to send
+        if serial.input.is_empty() && !serial.is_busy() && !self.send_mode {
+            // clear to send, the port picks it up on the next step
             if let Some(character) = self.serial_tx_buffer.pop_front() {
-                self.cpu.memory[0xFF08] = character as u8;
-                self.cpu.memory[0xFF09] = 1; // new data
+                serial.input.push_back(character as u8);
             }
         }
     }
     fn reset(&mut self) {
-        self.cpu = Processor::default();
-        let content = std::fs::read(&self.binary_path).unwrap_or_else(|_| panic!("Binary not found ({})!", self.binary_path));
-        for (index, byte) in content.iter().enumerate() {
-            self.cpu.memory[index] = *byte;
+        // the switches and buttons are on the board, so they stay where they were
+        let gpio_inputs = self.cpu.bus.device::<Gpio>().map(|gpio| gpio.inputs);
+        self.cpu = load_processor(&self.binary_path, &self.device_options);
+        if let Some(inputs) = gpio_inputs {
+            self.cpu.bus.device_mut::<Gpio>().expect("The gpio should always be on the bus").inputs = inputs;
         }
-        self.cpu.reset();
+        self.instruments.reset();
         self.serial_text.clear();
         self.serial_tx_buffer.clear();
+        self.memory_view.clear_history();
+        self.disassembly_view.rebuild(&self.cpu.bus.memory, &self.symbol_table);
+        self.last_fault = None;
+    }
+}
+
+/// makes a processor with the default devices and the binary loaded, then resets it
+fn load_processor(binary_path: &str, device_options: &DeviceOptions) -> Processor {
+    let content = std::fs::read(binary_path).unwrap_or_else(|_| panic!("Binary not found ({binary_path})!"));
+    load_binary(&content, device_options)
+}
+/// like load_processor, for a binary that's already in memory (like one that was just assembled)
+fn load_binary(content: &[u8], device_options: &DeviceOptions) -> Processor {
+    let mut cpu = Processor {
+        timing: device_options.timing.clone(),
+        stack_policy: device_options.stack_policy,
+        opcode_policy: device_options.opcode_policy,
+        ..Processor::default()
+    };
+    devices::register_defaults(&mut cpu.bus, device_options);
+    for (index, byte) in content.iter().enumerate() {
+        cpu.bus.memory[index] = *byte;
     }
+    cpu.reset();
+    cpu
 }
 
 fn push_to_string(string: &mut String, value_to_add: &str) {
@@ -426,15 +662,22 @@ fn push_to_string(string: &mut String, value_to_add: &str) {
     }
 }
 
-pub fn run(source_file: String) -> io::Result<()> {
+pub fn run(source_file: String, options: Options) -> io::Result<()> {
+    let device_options = options.open_devices();
     let mut terminal = ratatui::init();
-    let app_result = App::default().run(&mut terminal, source_file);
+    let mut app = App { options, ..App::default() };
+    let app_result = app.run(&mut terminal, source_file, device_options);
     ratatui::restore();
+    // after the terminal is restored so anything printed can be seen
+    app.instruments.finish(&app.cpu);
     app_result
 }
-pub fn run_with_symbol_table(binary_file: String, symbol_table_file: String) -> io::Result<()> {
+pub fn run_with_symbol_table(binary_file: String, symbol_table_file: String, options: Options) -> io::Result<()> {
+    let device_options = options.open_devices();
     let mut terminal = ratatui::init();
-    let app_result = App::default().run_with_symbol_table(&mut terminal, binary_file, symbol_table_file);
+    let mut app = App { options, ..App::default() };
+    let app_result = app.run_with_symbol_table(&mut terminal, binary_file, symbol_table_file, device_options);
     ratatui::restore();
+    app.instruments.finish(&app.cpu);
     app_result
 }
\ No newline at end of file
@@ -0,0 +1,103 @@
+use std::time::{Duration, Instant};
+
+/// instructions per second for each speed, where None runs as fast as possible
+const SPEEDS: [Option<u64>; 7] = [Some(10), Some(100), Some(1_000), Some(10_000), Some(100_000), Some(1_000_000), None];
+/// about what auto run used to do, one instruction per frame
+const DEFAULT_SPEED: usize = 1;
+/// how long to run for between redraws at max speed, so it still redraws a few times a second
+const MAX_SPEED_FRAME: Duration = Duration::from_millis(200);
+/// the longest a frame can run for at a set speed before it gives up on catching up
+const FRAME_LIMIT: Duration = Duration::from_millis(50);
+/// how often the measured speed is updated
+const MEASURE_WINDOW: Duration = Duration::from_secs(1);
+
+/// decides how many instructions auto run does each frame, so the speed doesn't depend on how often it redraws
+#[derive(Debug)]
+pub struct Pacer {
+    speed: usize,
+    /// instructions that are due but haven't been run, since a frame can only run whole ones
+    owed: f64,
+    last_frame: Option<Instant>,
+    /// when the measurement started and the instruction count then
+    window: Option<(Instant, u64)>,
+    /// instructions per second over the last window
+    measured: Option<f64>,
+}
+impl Default for Pacer {
+    fn default() -> Self {
+        Pacer {
+            speed: DEFAULT_SPEED,
+            owed: 0.0,
+            last_frame: None,
+            window: None,
+            measured: None,
+        }
+    }
+}
+impl Pacer {
+    pub fn faster(&mut self) {
+        self.speed = (self.speed + 1).min(SPEEDS.len() - 1);
+    }
+    pub fn slower(&mut self) {
+        self.speed = self.speed.saturating_sub(1);
+    }
+    /// forgets about the time auto run was stopped for, so it doesn't try to make up for it
+    pub fn stop(&mut self) {
+        self.owed = 0.0;
+        self.last_frame = None;
+        self.window = None;
+        self.measured = None;
+    }
+
+    /// how many instructions to run this frame, and the time to stop by if they take too long
+    pub fn frame(&mut self, now: Instant) -> (u64, Instant) {
+        let elapsed = self.last_frame.map_or(Duration::ZERO, |last_frame| now - last_frame);
+        self.last_frame = Some(now);
+        match SPEEDS[self.speed] {
+            Some(rate) => {
+                // the first frame runs one instruction, so starting auto run does something straight away
+                self.owed += if elapsed.is_zero() { 1.0 } else { elapsed.as_secs_f64() * rate as f64 };
+                let steps = self.owed.floor();
+                self.owed -= steps;
+                (steps as u64, now + FRAME_LIMIT)
+            }
+            None => (u64::MAX, now + MAX_SPEED_FRAME),
+        }
+    }
+    /// updates the measured speed from the number of instructions the cpu has run
+    pub fn measure(&mut self, now: Instant, instructions: u64) {
+        match self.window {
+            // the count goes back to 0 when the cpu is reset
+            Some((start, start_instructions)) if instructions >= start_instructions => {
+                let elapsed = now - start;
+                if elapsed >= MEASURE_WINDOW {
+                    self.measured = Some((instructions - start_instructions) as f64 / elapsed.as_secs_f64());
+                    self.window = Some((now, instructions));
+                }
+            }
+            _ => self.window = Some((now, instructions)),
+        }
+    }
+
+    /// like `1.0k instructions/s` or `max speed (running at 2.1M instructions/s)`
+    pub fn describe(&self) -> String {
+        let speed = match SPEEDS[self.speed] {
+            Some(rate) => format!("{} instructions/s", format_rate(rate as f64)),
+            None => "max speed".to_string(),
+        };
+        match self.measured {
+            Some(measured) => format!("{speed} (running at {} instructions/s)", format_rate(measured)),
+            None => speed,
+        }
+    }
+}
+
+fn format_rate(rate: f64) -> String {
+    if rate >= 1_000_000.0 {
+        format!("{:.1}M", rate / 1_000_000.0)
+    } else if rate >= 1_000.0 {
+        format!("{:.1}k", rate / 1_000.0)
+    } else {
+        format!("{rate:.0}")
+    }
+}
@@ -0,0 +1,61 @@
+use std::time::{Duration, Instant};
+use crate::simulator::{load_processor, Options};
+use crate::simulator::devices::serial::SerialPort;
+use crate::simulator::headless::{END_LOOP_END, END_LOOP_START};
+
+#[derive(Debug, Clone, Copy)]
+pub struct BenchmarkResult {
+    pub instructions: u64,
+    pub cycles: u64,
+    pub elapsed: Duration,
+    /// false if it stopped at the instruction limit
+    pub finished: bool,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+}
+impl BenchmarkResult {
+    /// millions of instructions per second
+    pub fn mips(&self) -> f64 {
+        self.instructions as f64 / self.elapsed.as_secs_f64() / 1_000_000.0
+    }
+    /// the clock speed the simulator kept up with, in MHz
+    pub fn simulated_mhz(&self) -> f64 {
+        self.cycles as f64 / self.elapsed.as_secs_f64() / 1_000_000.0
+    }
+}
+
+/// runs the binary as fast as possible with nothing watching it, until it reaches the end loop or the limit
+/// serial output is thrown away, since writing it out would be part of the time
+pub fn run(binary_path: &str, options: Options, max_instructions: u64, decode_cache: bool) -> BenchmarkResult {
+    let device_options = options.open_devices();
+    let mut cpu = load_processor(binary_path, &device_options);
+    cpu.decode_cache.enabled = decode_cache;
+    // counted here since the cpu's counts start over if it resets
+    let (mut instructions, mut cycles) = (0, 0);
+    let start = Instant::now();
+    let mut finished = false;
+    while instructions < max_instructions {
+        if (END_LOOP_START..=END_LOOP_END).contains(&cpu.program_counter) {
+            finished = true;
+            break;
+        }
+        let cycles_before = cpu.cycles;
+        cpu.step();
+        if cpu.fault.as_ref().is_some_and(|fault| fault.halted()) {
+            break;
+        }
+        instructions += 1;
+        cycles += cpu.cycles.saturating_sub(cycles_before);
+        if instructions % 4096 == 0 {
+            cpu.bus.device_mut::<SerialPort>().expect("The serial port should always be on the bus").take_output();
+        }
+    }
+    BenchmarkResult {
+        instructions,
+        cycles,
+        elapsed: start.elapsed(),
+        finished,
+        cache_hits: cpu.decode_cache.hits,
+        cache_misses: cpu.decode_cache.misses,
+    }
+}
@@ -94,286 +94,357 @@ pub enum Instruction {
     PushProgramCounter,
     PopProgramCounter,
     PopProgramCounterSubroutine,
+    SetInterruptEnable,
+    ClearInterruptEnable,
+    ReturnFromInterrupt,
 }
 
+impl Instruction {
+    /// every register the instruction names, including the index registers of its addresses
+    pub fn registers(&self) -> Vec<u8> {
+        let index = |address: &Address| address.index.into_iter().collect::<Vec<u8>>();
+        match self {
+            Instruction::Add(register, registers) | Instruction::Subtract(register, registers)
+            | Instruction::Xor(register, registers) | Instruction::Xnor(register, registers)
+            | Instruction::Or(register, registers) | Instruction::Nor(register, registers)
+            | Instruction::And(register, registers) | Instruction::Nand(register, registers) => {
+                register.iter().copied().chain(registers.iter().flat_map(|(one, two)| [*one, *two])).collect()
+            }
+            Instruction::PushRegisterToStack(register) | Instruction::PopRegisterFromStack(register)
+            | Instruction::CopyAccumulatorToRegister(register) | Instruction::CopyRegisterToAccumulator(register) => vec![*register],
+            Instruction::LoadAccumulator(address, _) => address.iter().flat_map(index).collect(),
+            Instruction::StoreAccumulator(address) | Instruction::BranchCarrySet(address)
+            | Instruction::BranchCarryClear(address) | Instruction::BranchNegative(address)
+            | Instruction::BranchPositive(address) | Instruction::BranchZero(address)
+            | Instruction::BranchNotZero(address) | Instruction::Jump(address) => index(address),
+            Instruction::BranchEqual(register, address) | Instruction::BranchNotEqual(register, address)
+            | Instruction::BranchGreater(register, address) | Instruction::BranchLess(register, address) => {
+                [*register].into_iter().chain(index(address)).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// registers are 00-07
+pub const REGISTER_COUNT: u8 = 8;
+/// the longest instructions are the register indexed branches (like ``beq``), an opcode and 4 operand bytes
+pub const MAX_INSTRUCTION_LENGTH: u16 = 5;
+/// ``phpc``, the first half of a ``jsr``
+pub const PHPC_OPCODE: u8 = 0x54;
+/// ``rts``
+pub const RTS_OPCODE: u8 = 0x57;
+/// ``rti``
+pub const RTI_OPCODE: u8 = 0x5E;
+
+/// why the bytes at an address aren't an instruction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParseError {
+    /// the opcode isn't in the ISA
+    InvalidOpcode(u8),
+    /// a register or index operand is past r7 (the opcode, then the register)
+    InvalidRegister(u8, u8),
+}
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::InvalidOpcode(opcode) => write!(f, "invalid opcode {opcode:02x}"),
+            ParseError::InvalidRegister(opcode, register) => write!(f, "invalid register {register:02x} for opcode {opcode:02x}"),
+        }
+    }
+}
+impl std::error::Error for ParseError {}
+
 /// returns the instruction, its parameters, and the number of additional bytes to skip (we automatically increment the program counter, so this is the number of bytes of parameters)
-pub fn parse_instruction(memory: &[u8; 65536], program_counter: u16) -> Result<(Instruction, u8), Box<dyn std::error::Error>> {
-    let program_counter = program_counter as usize;
-    match memory[program_counter] {
-        0x00 => Ok((Instruction::Noop, 0)),
+/// an instruction that names a register that doesn't exist is invalid, just like an opcode that doesn't exist
+pub fn parse_instruction(memory: &[u8; 65536], program_counter: u16) -> Result<(Instruction, u8), ParseError> {
+    let opcode = memory[program_counter as usize];
+    let (instruction, extra_bytes) = decode(memory, program_counter).ok_or(ParseError::InvalidOpcode(opcode))?;
+    if let Some(register) = instruction.registers().into_iter().find(|register| *register >= REGISTER_COUNT) {
+        return Err(ParseError::InvalidRegister(opcode, register));
+    }
+    Ok((instruction, extra_bytes))
+}
+
+fn decode(memory: &[u8; 65536], program_counter: u16) -> Option<(Instruction, u8)> {
+    // operands past ffff wrap around to the start of memory, like the address bus does
+    let operand = |offset: u16| memory[program_counter.wrapping_add(offset) as usize];
+    match memory[program_counter as usize] {
+        0x00 => Some((Instruction::Noop, 0)),
         0x01 => {
-            let parameter = memory[program_counter + 1];
-            Ok((Instruction::Add(Some(parameter), None), 1))
+            let parameter = operand(1);
+            Some((Instruction::Add(Some(parameter), None), 1))
         }
         0x02 => {
-            let parameter1 = memory[program_counter + 1];
-            let parameter2 = memory[program_counter + 2];
-            Ok((Instruction::Add(None, Some((parameter1, parameter2))), 2))
+            let parameter1 = operand(1);
+            let parameter2 = operand(2);
+            Some((Instruction::Add(None, Some((parameter1, parameter2))), 2))
         }
         0x03 => {
-            let parameter = memory[program_counter + 1];
-            Ok((Instruction::Subtract(Some(parameter), None), 1))
+            let parameter = operand(1);
+            Some((Instruction::Subtract(Some(parameter), None), 1))
         }
         0x04 => {
-            let parameter1 = memory[program_counter + 1];
-            let parameter2 = memory[program_counter + 2];
-            Ok((Instruction::Subtract(None, Some((parameter1, parameter2))), 2))
+            let parameter1 = operand(1);
+            let parameter2 = operand(2);
+            Some((Instruction::Subtract(None, Some((parameter1, parameter2))), 2))
         }
-        0x05 => Ok((Instruction::SetCarry, 0)),
-        0x06 => Ok((Instruction::ClearCarry, 0)),
+        0x05 => Some((Instruction::SetCarry, 0)),
+        0x06 => Some((Instruction::ClearCarry, 0)),
         0x07 => {
-            let parameter = memory[program_counter + 1];
-            Ok((Instruction::Xor(Some(parameter), None), 1))
+            let parameter = operand(1);
+            Some((Instruction::Xor(Some(parameter), None), 1))
         }
         0x08 => {
-            let parameter1 = memory[program_counter + 1];
-            let parameter2 = memory[program_counter + 2];
-            Ok((Instruction::Xor(None, Some((parameter1, parameter2))), 2))
+            let parameter1 = operand(1);
+            let parameter2 = operand(2);
+            Some((Instruction::Xor(None, Some((parameter1, parameter2))), 2))
         }
         0x09 => {
-            let parameter = memory[program_counter + 1];
-            Ok((Instruction::Xnor(Some(parameter), None), 1))
+            let parameter = operand(1);
+            Some((Instruction::Xnor(Some(parameter), None), 1))
         }
         0x0A => {
-            let parameter1 = memory[program_counter + 1];
-            let parameter2 = memory[program_counter + 2];
-            Ok((Instruction::Xnor(None, Some((parameter1, parameter2))), 2))
+            let parameter1 = operand(1);
+            let parameter2 = operand(2);
+            Some((Instruction::Xnor(None, Some((parameter1, parameter2))), 2))
         }
         0x0B => {
-            let parameter = memory[program_counter + 1];
-            Ok((Instruction::Or(Some(parameter), None), 1))
+            let parameter = operand(1);
+            Some((Instruction::Or(Some(parameter), None), 1))
         }
         0x0C => {
-            let parameter1 = memory[program_counter + 1];
-            let parameter2 = memory[program_counter + 2];
-            Ok((Instruction::Or(None, Some((parameter1, parameter2))), 2))
+            let parameter1 = operand(1);
+            let parameter2 = operand(2);
+            Some((Instruction::Or(None, Some((parameter1, parameter2))), 2))
         }
         0x0D => {
-            let parameter = memory[program_counter + 1];
-            Ok((Instruction::Nor(Some(parameter), None), 1))
+            let parameter = operand(1);
+            Some((Instruction::Nor(Some(parameter), None), 1))
         }
         0x0E => {
-            let parameter1 = memory[program_counter + 1];
-            let parameter2 = memory[program_counter + 2];
-            Ok((Instruction::Nor(None, Some((parameter1, parameter2))), 2))
+            let parameter1 = operand(1);
+            let parameter2 = operand(2);
+            Some((Instruction::Nor(None, Some((parameter1, parameter2))), 2))
         }
         0x0F => {
-            let parameter = memory[program_counter + 1];
-            Ok((Instruction::And(Some(parameter), None), 1))
+            let parameter = operand(1);
+            Some((Instruction::And(Some(parameter), None), 1))
         }
         0x10 => {
-            let parameter1 = memory[program_counter + 1];
-            let parameter2 = memory[program_counter + 2];
-            Ok((Instruction::And(None, Some((parameter1, parameter2))), 2))
+            let parameter1 = operand(1);
+            let parameter2 = operand(2);
+            Some((Instruction::And(None, Some((parameter1, parameter2))), 2))
         }
         0x11 => {
-            let parameter = memory[program_counter + 1];
-            Ok((Instruction::Nand(Some(parameter), None), 1))
+            let parameter = operand(1);
+            Some((Instruction::Nand(Some(parameter), None), 1))
         }
         0x12 => {
-            let parameter1 = memory[program_counter + 1];
-            let parameter2 = memory[program_counter + 2];
-            Ok((Instruction::Nand(None, Some((parameter1, parameter2))), 2))
-        }
-        0x13 => Ok((Instruction::Not, 0)),
-        0x14 => Ok((Instruction::RotateRight, 0)),
-        0x15 => Ok((Instruction::RotateLeft, 0)),
-        0x16 => Ok((Instruction::ShiftRight, 0)),
-        0x17 => Ok((Instruction::ShiftLeft, 0)),
+            let parameter1 = operand(1);
+            let parameter2 = operand(2);
+            Some((Instruction::Nand(None, Some((parameter1, parameter2))), 2))
+        }
+        0x13 => Some((Instruction::Not, 0)),
+        0x14 => Some((Instruction::RotateRight, 0)),
+        0x15 => Some((Instruction::RotateLeft, 0)),
+        0x16 => Some((Instruction::ShiftRight, 0)),
+        0x17 => Some((Instruction::ShiftLeft, 0)),
         0x21 => {
-            let parameter = memory[program_counter + 1];
-            Ok((Instruction::PushRegisterToStack(parameter), 1))
+            let parameter = operand(1);
+            Some((Instruction::PushRegisterToStack(parameter), 1))
         }
         0x22 => {
-            let parameter = memory[program_counter + 1];
-            Ok((Instruction::PopRegisterFromStack(parameter), 1))
+            let parameter = operand(1);
+            Some((Instruction::PopRegisterFromStack(parameter), 1))
         }
         0x23 => {
-            let parameter1 = memory[program_counter + 1];
-            let parameter2 = memory[program_counter + 2];
-            Ok((Instruction::LoadAccumulator(Some(Address::new_absolute(parameter1, parameter2)), None), 2))
+            let parameter1 = operand(1);
+            let parameter2 = operand(2);
+            Some((Instruction::LoadAccumulator(Some(Address::new_absolute(parameter1, parameter2)), None), 2))
         }
         0x24 => {
-            let parameter1 = memory[program_counter + 1];
-            let parameter2 = memory[program_counter + 2];
-            let parameter3 = memory[program_counter + 3];
-            Ok((Instruction::LoadAccumulator(Some(Address::new_indexed(parameter1, parameter2, parameter3)), None), 3))
+            let parameter1 = operand(1);
+            let parameter2 = operand(2);
+            let parameter3 = operand(3);
+            Some((Instruction::LoadAccumulator(Some(Address::new_indexed(parameter1, parameter2, parameter3)), None), 3))
         }
         0x25 => {
-            let parameter = memory[program_counter + 1];
-            Ok((Instruction::LoadAccumulator(Some(Address::new_zeropage(parameter)), None), 1))
+            let parameter = operand(1);
+            Some((Instruction::LoadAccumulator(Some(Address::new_zeropage(parameter)), None), 1))
         }
         0x26 => {
-            let parameter1 = memory[program_counter + 1];
-            let parameter2 = memory[program_counter + 2];
-            Ok((Instruction::LoadAccumulator(Some(Address::new_zeropage_indexed(parameter1, parameter2)), None), 2))
+            let parameter1 = operand(1);
+            let parameter2 = operand(2);
+            Some((Instruction::LoadAccumulator(Some(Address::new_zeropage_indexed(parameter1, parameter2)), None), 2))
         }
         0x27 => {
-            let parameter = memory[program_counter + 1];
-            Ok((Instruction::LoadAccumulator(None, Some(parameter)), 1))
+            let parameter = operand(1);
+            Some((Instruction::LoadAccumulator(None, Some(parameter)), 1))
         }
         0x28 => {
-            let parameter1 = memory[program_counter + 1];
-            let parameter2 = memory[program_counter + 2];
-            Ok((Instruction::StoreAccumulator(Address::new_absolute(parameter1, parameter2)), 2))
+            let parameter1 = operand(1);
+            let parameter2 = operand(2);
+            Some((Instruction::StoreAccumulator(Address::new_absolute(parameter1, parameter2)), 2))
         }
         0x29 => {
-            let parameter1 = memory[program_counter + 1];
-            let parameter2 = memory[program_counter + 2];
-            let parameter3 = memory[program_counter + 3];
-            Ok((Instruction::StoreAccumulator(Address::new_indexed(parameter1, parameter2, parameter3)), 3))
+            let parameter1 = operand(1);
+            let parameter2 = operand(2);
+            let parameter3 = operand(3);
+            Some((Instruction::StoreAccumulator(Address::new_indexed(parameter1, parameter2, parameter3)), 3))
         }
         0x2A => {
-            let parameter = memory[program_counter + 1];
-            Ok((Instruction::StoreAccumulator(Address::new_zeropage(parameter)), 1))
+            let parameter = operand(1);
+            Some((Instruction::StoreAccumulator(Address::new_zeropage(parameter)), 1))
         }
         0x2B => {
-            let parameter1 = memory[program_counter + 1];
-            let parameter2 = memory[program_counter + 2];
-            Ok((Instruction::StoreAccumulator(Address::new_zeropage_indexed(parameter1, parameter2)), 2))
+            let parameter1 = operand(1);
+            let parameter2 = operand(2);
+            Some((Instruction::StoreAccumulator(Address::new_zeropage_indexed(parameter1, parameter2)), 2))
         }
         0x2C => {
-            let parameter = memory[program_counter + 1];
-            Ok((Instruction::CopyAccumulatorToRegister(parameter), 1))
+            let parameter = operand(1);
+            Some((Instruction::CopyAccumulatorToRegister(parameter), 1))
         }
         0x2D => {
-            let parameter = memory[program_counter + 1];
-            Ok((Instruction::CopyRegisterToAccumulator(parameter), 1))
+            let parameter = operand(1);
+            Some((Instruction::CopyRegisterToAccumulator(parameter), 1))
         }
         0x42 => {
-            let parameter1 = memory[program_counter + 1];
-            let parameter2 = memory[program_counter + 2];
-            Ok((Instruction::BranchCarrySet(Address::new_absolute(parameter1, parameter2)), 2))
+            let parameter1 = operand(1);
+            let parameter2 = operand(2);
+            Some((Instruction::BranchCarrySet(Address::new_absolute(parameter1, parameter2)), 2))
         }
         0x43 => {
-            let parameter1 = memory[program_counter + 1];
-            let parameter2 = memory[program_counter + 2];
-            let parameter3 = memory[program_counter + 3];
-            Ok((Instruction::BranchCarrySet(Address::new_indexed(parameter1, parameter2, parameter3)), 3))
+            let parameter1 = operand(1);
+            let parameter2 = operand(2);
+            let parameter3 = operand(3);
+            Some((Instruction::BranchCarrySet(Address::new_indexed(parameter1, parameter2, parameter3)), 3))
         }
         0x44 => {
-            let parameter1 = memory[program_counter + 1];
-            let parameter2 = memory[program_counter + 2];
-            Ok((Instruction::BranchCarryClear(Address::new_absolute(parameter1, parameter2)), 2))
+            let parameter1 = operand(1);
+            let parameter2 = operand(2);
+            Some((Instruction::BranchCarryClear(Address::new_absolute(parameter1, parameter2)), 2))
         }
         0x45 => {
-            let parameter1 = memory[program_counter + 1];
-            let parameter2 = memory[program_counter + 2];
-            let parameter3 = memory[program_counter + 3];
-            Ok((Instruction::BranchCarryClear(Address::new_indexed(parameter1, parameter2, parameter3)), 3))
+            let parameter1 = operand(1);
+            let parameter2 = operand(2);
+            let parameter3 = operand(3);
+            Some((Instruction::BranchCarryClear(Address::new_indexed(parameter1, parameter2, parameter3)), 3))
         }
         0x46 => {
-            let parameter1 = memory[program_counter + 1];
-            let parameter2 = memory[program_counter + 2];
-            Ok((Instruction::BranchNegative(Address::new_absolute(parameter1, parameter2)), 2))
+            let parameter1 = operand(1);
+            let parameter2 = operand(2);
+            Some((Instruction::BranchNegative(Address::new_absolute(parameter1, parameter2)), 2))
         }
         0x47 => {
-            let parameter1 = memory[program_counter + 1];
-            let parameter2 = memory[program_counter + 2];
-            let parameter3 = memory[program_counter + 3];
-            Ok((Instruction::BranchNegative(Address::new_indexed(parameter1, parameter2, parameter3)), 3))
+            let parameter1 = operand(1);
+            let parameter2 = operand(2);
+            let parameter3 = operand(3);
+            Some((Instruction::BranchNegative(Address::new_indexed(parameter1, parameter2, parameter3)), 3))
         }
         0x48 => {
-            let parameter1 = memory[program_counter + 1];
-            let parameter2 = memory[program_counter + 2];
-            Ok((Instruction::BranchPositive(Address::new_absolute(parameter1, parameter2)), 2))
+            let parameter1 = operand(1);
+            let parameter2 = operand(2);
+            Some((Instruction::BranchPositive(Address::new_absolute(parameter1, parameter2)), 2))
         }
         0x49 => {
-            let parameter1 = memory[program_counter + 1];
-            let parameter2 = memory[program_counter + 2];
-            let parameter3 = memory[program_counter + 3];
-            Ok((Instruction::BranchPositive(Address::new_indexed(parameter1, parameter2, parameter3)), 3))
+            let parameter1 = operand(1);
+            let parameter2 = operand(2);
+            let parameter3 = operand(3);
+            Some((Instruction::BranchPositive(Address::new_indexed(parameter1, parameter2, parameter3)), 3))
         }
         0x4A => {
-            let parameter1 = memory[program_counter + 1];
-            let parameter2 = memory[program_counter + 2];
-            let parameter3 = memory[program_counter + 3];
-            Ok((Instruction::BranchEqual(parameter1, Address::new_absolute(parameter2, parameter3)), 3))
+            let parameter1 = operand(1);
+            let parameter2 = operand(2);
+            let parameter3 = operand(3);
+            Some((Instruction::BranchEqual(parameter1, Address::new_absolute(parameter2, parameter3)), 3))
         }
         0x4B => {
-            let parameter1 = memory[program_counter + 1];
-            let parameter2 = memory[program_counter + 2];
-            let parameter3 = memory[program_counter + 3];
-            let parameter4 = memory[program_counter + 4];
-            Ok((Instruction::BranchEqual(parameter1, Address::new_indexed(parameter2, parameter3, parameter4)), 3))
+            let parameter1 = operand(1);
+            let parameter2 = operand(2);
+            let parameter3 = operand(3);
+            let parameter4 = operand(4);
+            Some((Instruction::BranchEqual(parameter1, Address::new_indexed(parameter2, parameter3, parameter4)), 4))
         }
         0x4C => {
-            let parameter1 = memory[program_counter + 1];
-            let parameter2 = memory[program_counter + 2];
-            let parameter3 = memory[program_counter + 3];
-            Ok((Instruction::BranchNotEqual(parameter1, Address::new_absolute(parameter2, parameter3)), 3))
+            let parameter1 = operand(1);
+            let parameter2 = operand(2);
+            let parameter3 = operand(3);
+            Some((Instruction::BranchNotEqual(parameter1, Address::new_absolute(parameter2, parameter3)), 3))
         }
         0x4D => {
-            let parameter1 = memory[program_counter + 1];
-            let parameter2 = memory[program_counter + 2];
-            let parameter3 = memory[program_counter + 3];
-            let parameter4 = memory[program_counter + 4];
-            Ok((Instruction::BranchNotEqual(parameter1, Address::new_indexed(parameter2, parameter3, parameter4)), 3))
+            let parameter1 = operand(1);
+            let parameter2 = operand(2);
+            let parameter3 = operand(3);
+            let parameter4 = operand(4);
+            Some((Instruction::BranchNotEqual(parameter1, Address::new_indexed(parameter2, parameter3, parameter4)), 4))
         }
         0x4E => {
-            let parameter1 = memory[program_counter + 1];
-            let parameter2 = memory[program_counter + 2];
-            Ok((Instruction::BranchZero(Address::new_absolute(parameter1, parameter2)), 2))
+            let parameter1 = operand(1);
+            let parameter2 = operand(2);
+            Some((Instruction::BranchZero(Address::new_absolute(parameter1, parameter2)), 2))
         }
         0x4F => {
-            let parameter1 = memory[program_counter + 1];
-            let parameter2 = memory[program_counter + 2];
-            let parameter3 = memory[program_counter + 3];
-            Ok((Instruction::BranchZero(Address::new_indexed(parameter1, parameter2, parameter3)), 3))
+            let parameter1 = operand(1);
+            let parameter2 = operand(2);
+            let parameter3 = operand(3);
+            Some((Instruction::BranchZero(Address::new_indexed(parameter1, parameter2, parameter3)), 3))
         }
         0x50 => {
-            let parameter1 = memory[program_counter + 1];
-            let parameter2 = memory[program_counter + 2];
-            Ok((Instruction::BranchNotZero(Address::new_absolute(parameter1, parameter2)), 2))
+            let parameter1 = operand(1);
+            let parameter2 = operand(2);
+            Some((Instruction::BranchNotZero(Address::new_absolute(parameter1, parameter2)), 2))
         }
         0x51 => {
-            let parameter1 = memory[program_counter + 1];
-            let parameter2 = memory[program_counter + 2];
-            let parameter3 = memory[program_counter + 3];
-            Ok((Instruction::BranchNotZero(Address::new_indexed(parameter1, parameter2, parameter3)), 3))
+            let parameter1 = operand(1);
+            let parameter2 = operand(2);
+            let parameter3 = operand(3);
+            Some((Instruction::BranchNotZero(Address::new_indexed(parameter1, parameter2, parameter3)), 3))
         }
         0x52 => {
-            let parameter1 = memory[program_counter + 1];
-            let parameter2 = memory[program_counter + 2];
-            Ok((Instruction::Jump(Address::new_absolute(parameter1, parameter2)), 2))
+            let parameter1 = operand(1);
+            let parameter2 = operand(2);
+            Some((Instruction::Jump(Address::new_absolute(parameter1, parameter2)), 2))
         }
         0x53 => {
-            let parameter1 = memory[program_counter + 1];
-            let parameter2 = memory[program_counter + 2];
-            let parameter3 = memory[program_counter + 3];
-            Ok((Instruction::Jump(Address::new_indexed(parameter1, parameter2, parameter3)), 3))
-        }
-        0x54 => Ok((Instruction::PushProgramCounter, 0)),
-        0x55 => Ok((Instruction::PopProgramCounter, 0)),
-        0x57 => Ok((Instruction::PopProgramCounterSubroutine, 0)),
+            let parameter1 = operand(1);
+            let parameter2 = operand(2);
+            let parameter3 = operand(3);
+            Some((Instruction::Jump(Address::new_indexed(parameter1, parameter2, parameter3)), 3))
+        }
+        PHPC_OPCODE => Some((Instruction::PushProgramCounter, 0)),
+        0x55 => Some((Instruction::PopProgramCounter, 0)),
+        RTS_OPCODE => Some((Instruction::PopProgramCounterSubroutine, 0)),
         0x58 => {
-            let parameter1 = memory[program_counter + 1];
-            let parameter2 = memory[program_counter + 2];
-            let parameter3 = memory[program_counter + 3];
-            Ok((Instruction::BranchGreater(parameter1, Address::new_absolute(parameter2, parameter3)), 3))
+            let parameter1 = operand(1);
+            let parameter2 = operand(2);
+            let parameter3 = operand(3);
+            Some((Instruction::BranchGreater(parameter1, Address::new_absolute(parameter2, parameter3)), 3))
         }
         0x59 => {
-            let parameter1 = memory[program_counter + 1];
-            let parameter2 = memory[program_counter + 2];
-            let parameter3 = memory[program_counter + 3];
-            let parameter4 = memory[program_counter + 4];
-            Ok((Instruction::BranchGreater(parameter1, Address::new_indexed(parameter2, parameter3, parameter4)), 3))
+            let parameter1 = operand(1);
+            let parameter2 = operand(2);
+            let parameter3 = operand(3);
+            let parameter4 = operand(4);
+            Some((Instruction::BranchGreater(parameter1, Address::new_indexed(parameter2, parameter3, parameter4)), 4))
         }
         0x5A => {
-            let parameter1 = memory[program_counter + 1];
-            let parameter2 = memory[program_counter + 2];
-            let parameter3 = memory[program_counter + 3];
-            Ok((Instruction::BranchLess(parameter1, Address::new_absolute(parameter2, parameter3)), 3))
+            let parameter1 = operand(1);
+            let parameter2 = operand(2);
+            let parameter3 = operand(3);
+            Some((Instruction::BranchLess(parameter1, Address::new_absolute(parameter2, parameter3)), 3))
         }
         0x5B => {
-            let parameter1 = memory[program_counter + 1];
-            let parameter2 = memory[program_counter + 2];
-            let parameter3 = memory[program_counter + 3];
-            let parameter4 = memory[program_counter + 4];
-            Ok((Instruction::BranchLess(parameter1, Address::new_indexed(parameter2, parameter3, parameter4)), 3))
-        }
-        _ => {
-            Err(Box::from("parse_error"))
-        }
+            let parameter1 = operand(1);
+            let parameter2 = operand(2);
+            let parameter3 = operand(3);
+            let parameter4 = operand(4);
+            Some((Instruction::BranchLess(parameter1, Address::new_indexed(parameter2, parameter3, parameter4)), 4))
+        }
+        0x5C => Some((Instruction::SetInterruptEnable, 0)),
+        0x5D => Some((Instruction::ClearInterruptEnable, 0)),
+        RTI_OPCODE => Some((Instruction::ReturnFromInterrupt, 0)),
+        _ => None,
     }
 }
\ No newline at end of file
@@ -0,0 +1,160 @@
+use std::any::Any;
+use std::fmt::Debug;
+
+/// a memory mapped peripheral
+/// all addresses given to a device are offsets from the start of its range
+pub trait Device: Debug + Any {
+    fn read(&mut self, offset: u16) -> u8;
+    fn write(&mut self, offset: u16, value: u8);
+    /// reads a register without any side effects (used for displaying memory)
+    fn peek(&self, offset: u16) -> u8;
+    /// called after every instruction with the number of clock cycles the cpu has run since it was reset
+    /// (instructions take different numbers of cycles, so this can jump by more than one)
+    fn tick(&mut self, _cycle: u64) {}
+    /// called when the cpu is reset
+    fn reset(&mut self) {}
+    /// true while the device is holding the irq line (it stays up until the device is dealt with)
+    fn interrupt(&self) -> bool {
+        false
+    }
+    /// true once for every nmi the device raises, checked after every tick
+    fn take_nmi(&mut self) -> bool {
+        false
+    }
+    /// whether interrupt and take_nmi need to be checked, since checking every device after every instruction is slow
+    /// (asked once, when the device is registered)
+    fn irq_connected(&self) -> bool {
+        false
+    }
+    fn nmi_connected(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Debug)]
+struct MappedDevice {
+    start: u16,
+    end: u16,
+    device: Box<dyn Device>,
+    irq_connected: bool,
+    nmi_connected: bool,
+}
+
+/// routes the processor's loads and stores to either RAM or a device
+#[derive(Debug)]
+pub struct Bus {
+    /// writing to this directly skips the decode cache, so use write (or clear the cache) once the cpu is running
+    pub memory: Box<[u8; 65536]>,
+    devices: Vec<MappedDevice>,
+    /// whether any device can raise an nmi
+    nmi_connected: bool,
+    /// everything below this is ram, which lets most accesses skip looking through the devices
+    first_device: u16,
+    /// addresses and values written since the last step (used to highlight changes in the memory view)
+    pub writes: Vec<(u16, u8)>,
+    /// an irq raised from outside the devices (like the TUI), cleared when the cpu takes it
+    pub irq_requested: bool,
+    /// an nmi is waiting to be taken
+    pub nmi_pending: bool,
+}
+impl Default for Bus {
+    fn default() -> Self {
+        Bus::new()
+    }
+}
+impl Bus {
+    pub fn new() -> Bus {
+        Bus {
+            memory: Box::new([0; 65536]),
+            devices: Vec::new(),
+            nmi_connected: false,
+            first_device: 0xFFFF,
+            writes: Vec::with_capacity(2),
+            irq_requested: false,
+            nmi_pending: false,
+        }
+    }
+    /// maps a device to the addresses from start to end (inclusive)
+    pub fn register<T: Device>(&mut self, start: u16, end: u16, device: T) {
+        assert!(start <= end, "Device range {start:04x}-{end:04x} is backwards!");
+        for mapped in self.devices.iter() {
+            if start <= mapped.end && mapped.start <= end {
+                panic!("Device range {start:04x}-{end:04x} overlaps with {:04x}-{:04x}!", mapped.start, mapped.end);
+            }
+        }
+        self.first_device = self.first_device.min(start);
+        self.nmi_connected |= device.nmi_connected();
+        self.devices.push(MappedDevice {
+            start,
+            end,
+            irq_connected: device.irq_connected(),
+            nmi_connected: device.nmi_connected(),
+            device: Box::new(device),
+        });
+    }
+    pub fn read(&mut self, address: u16) -> u8 {
+        if address < self.first_device {
+            return self.memory[address as usize];
+        }
+        if let Some(mapped) = self.devices.iter_mut().find(|mapped| mapped.start <= address && address <= mapped.end) {
+            mapped.device.read(address - mapped.start)
+        } else {
+            self.memory[address as usize]
+        }
+    }
+    pub fn write(&mut self, address: u16, value: u8) {
+        self.writes.push((address, value));
+        if address < self.first_device {
+            self.memory[address as usize] = value;
+            return;
+        }
+        if let Some(mapped) = self.devices.iter_mut().find(|mapped| mapped.start <= address && address <= mapped.end) {
+            mapped.device.write(address - mapped.start, value);
+        } else {
+            self.memory[address as usize] = value;
+        }
+    }
+    pub fn peek(&self, address: u16) -> u8 {
+        if address < self.first_device {
+            return self.memory[address as usize];
+        }
+        if let Some(mapped) = self.devices.iter().find(|mapped| mapped.start <= address && address <= mapped.end) {
+            mapped.device.peek(address - mapped.start)
+        } else {
+            self.memory[address as usize]
+        }
+    }
+    pub fn tick(&mut self, cycle: u64) {
+        for mapped in self.devices.iter_mut() {
+            mapped.device.tick(cycle);
+        }
+        if self.nmi_connected {
+            for mapped in self.devices.iter_mut().filter(|mapped| mapped.nmi_connected) {
+                if mapped.device.take_nmi() {
+                    self.nmi_pending = true;
+                }
+            }
+        }
+    }
+    /// true if anything is holding the irq line
+    pub fn irq(&self) -> bool {
+        self.irq_requested || self.devices.iter().any(|mapped| mapped.irq_connected && mapped.device.interrupt())
+    }
+    pub fn reset(&mut self) {
+        self.irq_requested = false;
+        self.nmi_pending = false;
+        for mapped in self.devices.iter_mut() {
+            mapped.device.reset();
+        }
+    }
+    /// finds the first device of the given type
+    pub fn device<T: Device>(&self) -> Option<&T> {
+        self.devices.iter()
+            .find_map(|mapped| (mapped.device.as_ref() as &dyn Any).downcast_ref::<T>())
+    }
+    /// finds the first device of the given type
+    pub fn device_mut<T: Device>(&mut self) -> Option<&mut T> {
+        self.devices.iter_mut()
+            .find_map(|mapped| (mapped.device.as_mut() as &mut dyn Any).downcast_mut::<T>())
+    }
+}
@@ -0,0 +1,204 @@
+use crate::disassembler::symbols::{SymbolTable, SymbolType};
+use crate::simulator::bin_parser;
+use crate::simulator::bin_parser::{Instruction, PHPC_OPCODE, RTI_OPCODE, RTS_OPCODE};
+use crate::simulator::executor::Processor;
+use crate::simulator::trace::Labels;
+
+const STACK_PAGE: u16 = 0x0100;
+const JUMP_OPCODES: [u8; 2] = [0x52, 0x53];
+/// how far into a subroutine to look for the registers it pushed, so a frame that isn't really a subroutine doesn't
+/// scan through all of memory
+const MAX_PUSH_SCAN: u16 = 0x400;
+
+/// a subroutine call found on the stack page
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CallFrame {
+    /// address of the phpc that made the call (the jsr)
+    pub call_address: u16,
+    /// where the call jumped to
+    pub target: u16,
+    /// offset of the low byte of the return address on the stack page
+    pub stack_offset: u8,
+}
+
+/// a change in the call depth from one instruction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CallChange {
+    /// a call to the subroutine or interrupt handler at the address
+    Call(u16),
+    Return,
+}
+
+/// follows subroutine calls one instruction at a time, for anything that keeps its own call depth or stack
+/// a jsr is a phpc followed by a jmp, and the callee starts once the jmp has run (a phpc on its own isn't a call)
+/// starting an interrupt is a call to its handler, and rti returns from it
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CallTracker {
+    last_was_phpc: bool,
+}
+impl CallTracker {
+    /// call once the instruction with this opcode has run, with the cpu after the step
+    /// there can be two changes, since an interrupt can start right after a call or return
+    pub fn step(&mut self, opcode: u8, cpu: &Processor) -> [Option<CallChange>; 2] {
+        let change = if self.last_was_phpc && JUMP_OPCODES.contains(&opcode) {
+            // an interrupt right after the jump pushed where it went
+            let target = match cpu.interrupt {
+                Some(_) => interrupted_program_counter(cpu),
+                None => cpu.program_counter,
+            };
+            Some(CallChange::Call(target))
+        } else if opcode == RTS_OPCODE || opcode == RTI_OPCODE {
+            Some(CallChange::Return)
+        } else {
+            None
+        };
+        self.last_was_phpc = opcode == PHPC_OPCODE;
+        let interrupt = cpu.interrupt.map(|_| {
+            self.last_was_phpc = false;
+            CallChange::Call(cpu.program_counter)
+        });
+        [change, interrupt]
+    }
+    pub fn reset(&mut self) {
+        self.last_was_phpc = false;
+    }
+}
+
+/// where the program was when the interrupt that just started came in
+/// (the program counter is pushed low byte first, then the status register)
+fn interrupted_program_counter(cpu: &Processor) -> u16 {
+    let high_byte = cpu.bus.peek(STACK_PAGE + cpu.stack_pointer.wrapping_sub(2) as u16);
+    let low_byte = cpu.bus.peek(STACK_PAGE + cpu.stack_pointer.wrapping_sub(3) as u16);
+    ((high_byte as u16) << 8) | low_byte as u16
+}
+
+/// finds the subroutine calls on the stack, innermost first
+/// jsr pushes the address of its phpc (low byte first) and then jumps, so anything on the stack that points at a
+/// phpc followed by a jmp is treated as a call, and everything else is a pushed register
+pub fn reconstruct(cpu: &Processor) -> Vec<CallFrame> {
+    let mut frames = Vec::new();
+    let mut offset = cpu.stack_pointer as u16;
+    while offset >= 2 {
+        let high_byte = cpu.bus.peek(STACK_PAGE + offset - 1);
+        let low_byte = cpu.bus.peek(STACK_PAGE + offset - 2);
+        let call_address = ((high_byte as u16) << 8) | low_byte as u16;
+        if let Some(target) = call_target(cpu, call_address) {
+            frames.push(CallFrame {
+                call_address,
+                target,
+                stack_offset: (offset - 2) as u8,
+            });
+            offset -= 2;
+        } else {
+            offset -= 1;
+        }
+    }
+    frames
+}
+
+/// where the jsr at the given address jumps to, if it really is a jsr
+fn call_target(cpu: &Processor, call_address: u16) -> Option<u16> {
+    // phpc + a jmp with an index is the longest a jsr can be
+    if call_address > 0xFFFF - 4 || cpu.bus.memory[call_address as usize] != PHPC_OPCODE {
+        return None;
+    }
+    match bin_parser::parse_instruction(&cpu.bus.memory, call_address + 1) {
+        Ok((bin_parser::Instruction::Jump(address), _)) => {
+            // the parser already rejects registers past r7, but this runs on whatever is in memory
+            let index = match address.index {
+                Some(index) => *cpu.registers.get(index as usize)? as u16,
+                None => 0,
+            };
+            Some(address.address.wrapping_add(index))
+        }
+        _ => None,
+    }
+}
+
+/// the name of the subroutine (or label) at an address, without the _SR the assembler adds
+pub fn subroutine_name(symbol_table: &SymbolTable, target: u16) -> String {
+    match symbol_table.symbols.get(&target) {
+        Some(symbol) if symbol.symbol_type == SymbolType::Label => {
+            let name = symbol.name.rsplit_once('/').unwrap_or(("", &symbol.name)).1;
+            name.strip_suffix("_SR").unwrap_or(name).to_string()
+        }
+        _ => format!("sub_{target:04x}"),
+    }
+}
+
+/// a frame in the call stack, with the bytes it pushed on top of its return address
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackFrame {
+    /// the subroutine, or the label before the code for the outermost frame
+    pub name: String,
+    /// where it's running (for the innermost frame) or where it made the call to the next frame in
+    pub location: u16,
+    pub pushed: Vec<PushedByte>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PushedByte {
+    pub value: u8,
+    /// the register the phr was for, if the subroutine's pushes could be matched up with the stack
+    pub register: Option<u8>,
+}
+
+/// the whole call stack, innermost first, ending with the code that isn't in a subroutine
+/// like `SEND_BYTE` with `r0 = 48`, called from `echo_user_char`, called from `serialtest.LOOP+3`
+pub fn frames(cpu: &Processor, symbol_table: &SymbolTable) -> Vec<StackFrame> {
+    let calls = reconstruct(cpu);
+    let mut frames = Vec::with_capacity(calls.len() + 1);
+    // a frame's pushes go from just above its return address up to the next frame's return address
+    let mut top = cpu.stack_pointer as u16;
+    let mut location = cpu.program_counter;
+    for call in calls.iter() {
+        let bottom = call.stack_offset as u16 + 2;
+        frames.push(StackFrame {
+            name: subroutine_name(symbol_table, call.target),
+            location,
+            pushed: pushed_bytes(cpu, bottom, top, pushed_registers(cpu, call.target, location)),
+        });
+        top = call.stack_offset as u16;
+        location = call.call_address;
+    }
+    let name = Labels::new(symbol_table).name_at(location);
+    frames.push(StackFrame {
+        name: if name.is_empty() { "main".to_string() } else { name },
+        location,
+        pushed: pushed_bytes(cpu, 0, top, None),
+    });
+    frames
+}
+
+/// the bytes on the stack page from `bottom` up to (not including) `top`, oldest first
+fn pushed_bytes(cpu: &Processor, bottom: u16, top: u16, registers: Option<Vec<u8>>) -> Vec<PushedByte> {
+    let values = (bottom..top).map(|offset| cpu.bus.peek(STACK_PAGE + offset)).collect::<Vec<u8>>();
+    // only trust the registers if there's one for every byte
+    let registers = registers.filter(|registers| registers.len() == values.len());
+    values.iter().enumerate().map(|(index, value)| PushedByte {
+        value: *value,
+        register: registers.as_ref().map(|registers| registers[index]),
+    }).collect()
+}
+
+/// the registers still pushed when a subroutine gets to `location`, found by going through its code in order
+/// this doesn't follow branches, so it's only a guess, but most subroutines push at the start and pop at the end
+fn pushed_registers(cpu: &Processor, start: u16, location: u16) -> Option<Vec<u8>> {
+    if location < start || location - start > MAX_PUSH_SCAN {
+        return None;
+    }
+    let mut registers = Vec::new();
+    let mut address = start;
+    while address < location {
+        let (instruction, operand_bytes) = bin_parser::parse_instruction(&cpu.bus.memory, address).ok()?;
+        match instruction {
+            Instruction::PushRegisterToStack(register) => registers.push(register),
+            Instruction::PopRegisterFromStack(_) => {
+                registers.pop();
+            }
+            _ => {}
+        }
+        address = address.checked_add(1 + operand_bytes as u16)?;
+    }
+    Some(registers)
+}
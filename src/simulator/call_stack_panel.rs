@@ -0,0 +1,31 @@
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Paragraph, Wrap};
+use crate::simulator::call_stack::{PushedByte, StackFrame};
+
+/// the call stack, innermost first, with what each frame has pushed under it
+pub fn widget(frames: &[StackFrame]) -> Paragraph<'static> {
+    let mut lines: Vec<Line> = Vec::new();
+    for (index, frame) in frames.iter().enumerate() {
+        let prefix = if index == 0 { "in " } else { "called from " };
+        lines.push(Line::from(vec![
+            prefix.into(),
+            frame.name.clone().light_blue().bold(),
+            format!(" ({:04x})", frame.location).yellow(),
+        ]));
+        if !frame.pushed.is_empty() {
+            let pushed = frame.pushed.iter().map(describe).collect::<Vec<String>>().join(", ");
+            lines.push(Line::from(vec!["  pushed ".into(), pushed.green()]));
+        }
+    }
+    Paragraph::new(lines)
+        .block(Block::bordered().title(" Call Stack "))
+        .wrap(Wrap { trim: false })
+}
+
+/// like `r1 = 48`, or just `48` if it isn't known which register it was
+fn describe(byte: &PushedByte) -> String {
+    match byte.register {
+        Some(register) => format!("r{register} = {:02x}", byte.value),
+        None => format!("{:02x}", byte.value),
+    }
+}
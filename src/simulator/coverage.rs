@@ -0,0 +1,199 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use crate::disassembler::symbols::SymbolTable;
+use crate::simulator::bin_parser;
+use crate::simulator::bin_parser::Instruction;
+use crate::simulator::executor::Processor;
+
+/// what to do with the coverage, from the command line
+#[derive(Debug, Default, Clone)]
+pub struct CoverageOptions {
+    /// where to write the lcov report, a summary is always printed
+    pub lcov: Option<String>,
+    /// the directory the source files in the symbol table are relative to
+    pub source_directory: PathBuf,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct BranchCounts {
+    taken: u64,
+    not_taken: u64,
+}
+
+#[derive(Debug, Default)]
+struct FileCoverage {
+    /// line -> times its first instruction ran
+    lines: BTreeMap<u32, u64>,
+    /// line -> the branch on it, if it has one (and it ran)
+    branches: BTreeMap<u32, Option<BranchCounts>>,
+}
+impl FileCoverage {
+    fn lines_hit(&self) -> usize {
+        self.lines.values().filter(|count| **count > 0).count()
+    }
+    /// every branch has two outcomes, taken and not taken
+    fn branch_outcomes(&self) -> (usize, usize) {
+        let hit = self.branches.values()
+            .map(|counts| counts.map_or(0, |counts| (counts.taken > 0) as usize + (counts.not_taken > 0) as usize))
+            .sum();
+        (hit, self.branches.len() * 2)
+    }
+}
+
+/// records which instruction bytes ran and which way each conditional branch went
+#[derive(Debug)]
+pub struct Coverage {
+    options: CoverageOptions,
+    symbol_table: SymbolTable,
+    /// times each address ran as the start of an instruction
+    executions: Box<[u64; 65536]>,
+    executed_bytes: Box<[bool; 65536]>,
+    branches: BTreeMap<u16, BranchCounts>,
+    // the instruction that's running
+    program_counter: u16,
+    length: u16,
+    is_branch: bool,
+}
+impl Coverage {
+    pub fn new(options: &CoverageOptions, symbol_table: &SymbolTable) -> Coverage {
+        Coverage {
+            options: options.clone(),
+            symbol_table: symbol_table.clone(),
+            executions: Box::new([0; 65536]),
+            executed_bytes: Box::new([false; 65536]),
+            branches: BTreeMap::new(),
+            program_counter: 0,
+            length: 1,
+            is_branch: false,
+        }
+    }
+
+    pub fn before_step(&mut self, cpu: &Processor) {
+        self.program_counter = cpu.program_counter;
+        (self.length, self.is_branch) = match bin_parser::parse_instruction(&cpu.bus.memory, cpu.program_counter) {
+            Ok((instruction, extra_bytes)) => (extra_bytes as u16 + 1, is_conditional_branch(&instruction)),
+            Err(_) => (1, false),
+        };
+    }
+    pub fn after_step(&mut self, cpu: &Processor) {
+        // the instruction didn't run
+        if cpu.fault.as_ref().is_some_and(|fault| fault.halted()) {
+            return;
+        }
+        self.executions[self.program_counter as usize] += 1;
+        for offset in 0..self.length {
+            self.executed_bytes[self.program_counter.wrapping_add(offset) as usize] = true;
+        }
+        if self.is_branch {
+            let counts = self.branches.entry(self.program_counter).or_default();
+            // a branch to the next instruction counts as not taken, since it can't be told apart
+            if next_instruction(cpu) == self.program_counter.wrapping_add(self.length) {
+                counts.not_taken += 1;
+            } else {
+                counts.taken += 1;
+            }
+        }
+    }
+
+    /// groups the counts by source file and line, using the instruction at the start of each line
+    fn by_file(&self, cpu: &Processor) -> BTreeMap<String, FileCoverage> {
+        let mut files = BTreeMap::<String, FileCoverage>::new();
+        for (address, source_line) in self.symbol_table.lines.iter() {
+            let file = files.entry(source_line.file.clone()).or_default();
+            *file.lines.entry(source_line.line).or_default() += self.executions[*address as usize];
+            let is_branch = bin_parser::parse_instruction(&cpu.bus.memory, *address)
+                .is_ok_and(|(instruction, _)| is_conditional_branch(&instruction));
+            if is_branch {
+                file.branches.insert(source_line.line, self.branches.get(address).copied());
+            }
+        }
+        files
+    }
+
+    /// writes the lcov report (if there is one) and prints the summary
+    pub fn write(&self, cpu: &Processor) {
+        let files = self.by_file(cpu);
+        if let Some(path) = &self.options.lcov {
+            let file = File::create(path).unwrap_or_else(|error| panic!("Could not create \"{path}\" ({error})!"));
+            let mut writer = BufWriter::new(file);
+            self.write_lcov(&mut writer, &files).unwrap_or_else(|error| panic!("Failed to write the coverage ({error})!"));
+        }
+        eprint!("{}", self.summary(&files));
+    }
+
+    fn write_lcov(&self, writer: &mut impl Write, files: &BTreeMap<String, FileCoverage>) -> std::io::Result<()> {
+        writeln!(writer, "TN:")?;
+        for (name, file) in files {
+            let path = self.options.source_directory.join(name);
+            writeln!(writer, "SF:{}", std::fs::canonicalize(&path).unwrap_or(path).display())?;
+            for (line, count) in file.lines.iter() {
+                writeln!(writer, "DA:{line},{count}")?;
+            }
+            for (line, counts) in file.branches.iter() {
+                // branch 0 is taken, branch 1 is not taken, and - means the branch never ran
+                let (taken, not_taken) = counts.map_or(("-".to_string(), "-".to_string()), |counts| {
+                    (counts.taken.to_string(), counts.not_taken.to_string())
+                });
+                writeln!(writer, "BRDA:{line},0,0,{taken}")?;
+                writeln!(writer, "BRDA:{line},0,1,{not_taken}")?;
+            }
+            let (branches_hit, branches_found) = file.branch_outcomes();
+            writeln!(writer, "BRF:{branches_found}")?;
+            writeln!(writer, "BRH:{branches_hit}")?;
+            writeln!(writer, "LF:{}", file.lines.len())?;
+            writeln!(writer, "LH:{}", file.lines_hit())?;
+            writeln!(writer, "end_of_record")?;
+        }
+        Ok(())
+    }
+
+    fn summary(&self, files: &BTreeMap<String, FileCoverage>) -> String {
+        let percent = |hit: usize, found: usize| if found == 0 { 100.0 } else { hit as f64 * 100.0 / found as f64 };
+        let executed_bytes = self.executed_bytes.iter().filter(|executed| **executed).count();
+        let mut summary = format!("Coverage ({executed_bytes} instruction bytes executed):\n");
+        if files.is_empty() {
+            summary += "  No source lines to map to (is the symbol table loaded?)\n";
+            return summary;
+        }
+        let width = files.keys().map(String::len).max().unwrap_or(0).max("total".len());
+        summary += &format!("  {:<width$}  {:>18}  {:>18}\n", "file", "lines", "branches");
+        let (mut total_lines, mut total_lines_hit, mut total_branches, mut total_branches_hit) = (0, 0, 0, 0);
+        let row = |name: &str, lines_hit: usize, lines: usize, branches_hit: usize, branches: usize| {
+            format!("  {name:<width$}  {:>11} {:>5.1}%  {:>11} {:>5.1}%\n",
+                    format!("{lines_hit}/{lines}"), percent(lines_hit, lines),
+                    format!("{branches_hit}/{branches}"), percent(branches_hit, branches))
+        };
+        for (name, file) in files {
+            let (branches_hit, branches) = file.branch_outcomes();
+            summary += &row(name, file.lines_hit(), file.lines.len(), branches_hit, branches);
+            total_lines += file.lines.len();
+            total_lines_hit += file.lines_hit();
+            total_branches += branches;
+            total_branches_hit += branches_hit;
+        }
+        summary += &row("total", total_lines_hit, total_lines, total_branches_hit, total_branches);
+        summary
+    }
+}
+
+/// where the program went after the step, which is on the stack if an interrupt started right after it
+fn next_instruction(cpu: &Processor) -> u16 {
+    if cpu.interrupt.is_none() {
+        return cpu.program_counter;
+    }
+    // the program counter is pushed low byte first, then the status register
+    let high_byte = cpu.bus.peek(0x0100 + cpu.stack_pointer.wrapping_sub(2) as u16);
+    let low_byte = cpu.bus.peek(0x0100 + cpu.stack_pointer.wrapping_sub(3) as u16);
+    ((high_byte as u16) << 8) | low_byte as u16
+}
+
+fn is_conditional_branch(instruction: &Instruction) -> bool {
+    matches!(instruction,
+        Instruction::BranchCarrySet(_) | Instruction::BranchCarryClear(_)
+        | Instruction::BranchNegative(_) | Instruction::BranchPositive(_)
+        | Instruction::BranchEqual(_, _) | Instruction::BranchNotEqual(_, _)
+        | Instruction::BranchZero(_) | Instruction::BranchNotZero(_)
+        | Instruction::BranchGreater(_, _) | Instruction::BranchLess(_, _))
+}
@@ -0,0 +1,625 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use serde_json::{json, Value};
+use crate::disassembler::symbols::SymbolTable;
+use crate::simulator::{call_stack, load_processor, memory_view, Options};
+use crate::simulator::fault::{FaultOutcome, OpcodePolicy, StackPolicy};
+use crate::simulator::devices::DeviceOptions;
+use crate::simulator::devices::serial::SerialPort;
+use crate::simulator::executor::Processor;
+use crate::simulator::call_stack::{CallChange, CallTracker};
+use crate::simulator::headless::{END_LOOP_END, END_LOOP_START};
+
+/// how many instructions to run between checking for requests (like pause) while running
+const RUN_CHUNK: u32 = 4096;
+const THREAD_ID: u64 = 1;
+
+// variable references for the scopes
+const REGISTERS_REFERENCE: u64 = 1;
+const FLAGS_REFERENCE: u64 = 2;
+const DEFINES_REFERENCE: u64 = 3;
+
+const FLAGS: [(&str, u8); 7] = [
+    ("carry", 0b100000_00),
+    ("zero", 0b010000_00),
+    ("greater", 0b001000_00),
+    ("less", 0b000100_00),
+    ("equal", 0b000010_00),
+    ("negative", 0b000001_00),
+    ("interrupt enable", 0b000000_10),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RunMode {
+    Continue,
+    /// run until the start of a different line
+    StepIn { from_line: Option<(usize, u32)> },
+    /// like step in, but don't stop inside subroutines that are called along the way
+    StepOver { from_line: Option<(usize, u32)>, call_depth: i64 },
+    /// run until the current subroutine returns
+    StepOut { call_depth: i64 },
+}
+
+/// a program that has been launched
+#[derive(Debug)]
+struct Target {
+    cpu: Processor,
+    binary_path: String,
+    device_options: DeviceOptions,
+    symbol_table: SymbolTable,
+    /// the files in the symbol table, resolved to real paths (the index is used to refer to them)
+    files: Vec<(String, PathBuf)>,
+    stop_on_entry: bool,
+    /// number of jsr's minus rts's since reset, used for stepping over and out of subroutines
+    call_depth: i64,
+    calls: CallTracker,
+}
+
+#[derive(Debug)]
+struct DapSession {
+    seq: u64,
+    target: Option<Target>,
+    configured: bool,
+    started: bool,
+    running: Option<RunMode>,
+    /// the requested lines for each source file, so they can be resolved again after a launch
+    requested_breakpoints: HashMap<PathBuf, Vec<u32>>,
+    /// address -> breakpoint id
+    breakpoints: HashMap<u16, u64>,
+    next_breakpoint_id: u64,
+    finished: bool,
+}
+
+/// runs a debug adapter over stdio until the client disconnects
+pub fn serve() {
+    let requests = spawn_reader();
+    let mut session = DapSession {
+        seq: 1,
+        target: None,
+        configured: false,
+        started: false,
+        running: None,
+        requested_breakpoints: HashMap::new(),
+        breakpoints: HashMap::new(),
+        next_breakpoint_id: 1,
+        finished: false,
+    };
+    while !session.finished {
+        let request = if session.running.is_some() {
+            match requests.try_recv() {
+                Ok(request) => Some(request),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => return,
+            }
+        } else {
+            match requests.recv() {
+                Ok(request) => Some(request),
+                Err(_) => return,
+            }
+        };
+        if let Some(request) = request {
+            session.handle(&request);
+        }
+        if session.running.is_some() {
+            session.run_chunk();
+        }
+    }
+}
+
+/// reads messages from stdin on another thread, so requests can arrive while the program is running
+fn spawn_reader() -> Receiver<Value> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut stdin = BufReader::new(std::io::stdin());
+        loop {
+            let mut content_length = None;
+            loop {
+                let mut header = String::new();
+                if stdin.read_line(&mut header).unwrap_or(0) == 0 {
+                    return;
+                }
+                let header = header.trim();
+                if header.is_empty() {
+                    break;
+                }
+                if let Some(length) = header.strip_prefix("Content-Length:") {
+                    content_length = length.trim().parse::<usize>().ok();
+                }
+            }
+            let Some(content_length) = content_length else {
+                continue;
+            };
+            let mut content = vec![0; content_length];
+            if stdin.read_exact(&mut content).is_err() {
+                return;
+            }
+            match serde_json::from_slice(&content) {
+                Ok(message) => {
+                    if sender.send(message).is_err() {
+                        return;
+                    }
+                }
+                Err(error) => eprintln!("WARNING: Got a message that isn't valid json ({error})"),
+            }
+        }
+    });
+    receiver
+}
+
+impl DapSession {
+    fn handle(&mut self, request: &Value) {
+        let command = request["command"].as_str().unwrap_or_default();
+        let arguments = &request["arguments"];
+        let result = match command {
+            "initialize" => Ok(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsEvaluateForHovers": true,
+                "supportsRestartRequest": true,
+                "supportsTerminateRequest": true,
+            })),
+            "launch" => self.launch(arguments),
+            "setBreakpoints" => self.set_breakpoints(arguments),
+            "setExceptionBreakpoints" => Ok(json!({ "breakpoints": [] })),
+            "configurationDone" => {
+                self.configured = true;
+                Ok(Value::Null)
+            }
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "GoldCore" }] })),
+            "stackTrace" => self.stack_trace(),
+            "scopes" => Ok(json!({ "scopes": [
+                { "name": "Registers", "variablesReference": REGISTERS_REFERENCE, "expensive": false },
+                { "name": "Flags", "variablesReference": FLAGS_REFERENCE, "expensive": false },
+                { "name": "Defines", "variablesReference": DEFINES_REFERENCE, "expensive": false },
+            ]})),
+            "variables" => self.variables(arguments["variablesReference"].as_u64().unwrap_or(0)),
+            "evaluate" => self.evaluate(arguments["expression"].as_str().unwrap_or_default()),
+            "continue" => {
+                let mode = self.target.as_ref().map(|_| RunMode::Continue);
+                self.resume_with(mode).map(|_| json!({ "allThreadsContinued": true }))
+            }
+            "next" => {
+                let mode = self.target.as_ref().map(|target| RunMode::StepOver {
+                    from_line: target.current_line(),
+                    call_depth: target.call_depth,
+                });
+                self.resume_with(mode)
+            }
+            "stepIn" => {
+                let mode = self.target.as_ref().map(|target| RunMode::StepIn { from_line: target.current_line() });
+                self.resume_with(mode)
+            }
+            "stepOut" => {
+                let mode = self.target.as_ref().map(|target| RunMode::StepOut { call_depth: target.call_depth });
+                self.resume_with(mode)
+            }
+            "pause" => {
+                if self.running.take().is_some() {
+                    self.send_response(request, Ok(Value::Null));
+                    self.send_stopped("pause", None);
+                    return;
+                }
+                Ok(Value::Null)
+            }
+            "restart" => self.restart(),
+            "disconnect" | "terminate" => {
+                self.finished = true;
+                self.send_response(request, Ok(Value::Null));
+                self.send_event("terminated", Value::Null);
+                return;
+            }
+            _ => Err(format!("Unsupported request \"{command}\"")),
+        };
+        self.send_response(request, result);
+
+        if command == "initialize" {
+            self.send_event("initialized", Value::Null);
+        }
+        // the program starts once it's been launched and the client has sent its breakpoints, whichever is last
+        if !self.started && self.configured && let Some(target) = &self.target {
+            self.started = true;
+            if target.stop_on_entry {
+                self.send_stopped("entry", None);
+            } else {
+                self.running = Some(RunMode::Continue);
+            }
+        }
+    }
+
+    fn launch(&mut self, arguments: &Value) -> Result<Value, String> {
+        let binary_path = arguments["program"].as_str().ok_or("\"program\" must be the path to a binary")?.to_string();
+        if !Path::new(&binary_path).exists() {
+            return Err(format!("Binary not found ({binary_path})"));
+        }
+        let table_path = arguments["symbols"].as_str().map(str::to_string)
+            .unwrap_or_else(|| Path::new(&binary_path).with_extension("symbols").to_string_lossy().to_string());
+        let symbol_table = match std::fs::read(&table_path) {
+            Ok(bytes) => SymbolTable::from_bytes(&bytes),
+            Err(_) => {
+                self.send_output("console", &format!("No symbol table at {table_path}, source breakpoints won't work\n"));
+                SymbolTable::new()
+            }
+        };
+        // source files are relative to where the symbol table was written
+        let source_directory = Path::new(&table_path).parent().map(Path::to_path_buf).unwrap_or_default();
+        let mut files = symbol_table.lines.values()
+            .map(|line| line.file.clone())
+            .collect::<Vec<String>>();
+        files.sort();
+        files.dedup();
+        let files = files.into_iter()
+            .map(|file| {
+                let path = source_directory.join(&file);
+                let path = std::fs::canonicalize(&path).unwrap_or(path);
+                (file, path)
+            })
+            .collect();
+
+        let options = Options {
+            serial_input: arguments["serialInput"].as_str().map(str::to_string),
+            serial_log: arguments["serialLog"].as_str().map(str::to_string),
+            stack_policy: match arguments["stackFault"].as_str() {
+                Some(policy) => StackPolicy::parse(policy).ok_or(format!("Unknown stack fault policy \"{policy}\" (halt, wrap, or reset)"))?,
+                None => StackPolicy::default(),
+            },
+            opcode_policy: match arguments["invalidOpcode"].as_str() {
+                Some(policy) => OpcodePolicy::parse(policy).ok_or(format!("Unknown invalid opcode policy \"{policy}\" (reset or trap)"))?,
+                None => OpcodePolicy::default(),
+            },
+            rng_seed: match arguments["rngSeed"].as_u64() {
+                Some(seed) => Some(u16::try_from(seed).map_err(|_| format!("The RNG seed has to be from 0 to 65535 (got {seed})"))?),
+                None => None,
+            },
+            ..Options::default()
+        };
+        let device_options = options.open_devices();
+        self.target = Some(Target {
+            cpu: load_processor(&binary_path, &device_options),
+            binary_path,
+            device_options,
+            symbol_table,
+            files,
+            stop_on_entry: arguments["stopOnEntry"].as_bool().unwrap_or(false),
+            call_depth: 0,
+            calls: CallTracker::default(),
+        });
+        // breakpoints can be set before launching, when we don't know where the lines are yet
+        let requested = self.requested_breakpoints.clone();
+        for (path, lines) in requested {
+            let breakpoints = self.resolve_breakpoints(&path, &lines);
+            for breakpoint in breakpoints.iter().filter(|breakpoint| breakpoint["verified"] == true) {
+                self.send_event("breakpoint", json!({ "reason": "changed", "breakpoint": breakpoint }));
+            }
+        }
+        Ok(Value::Null)
+    }
+
+    fn restart(&mut self) -> Result<Value, String> {
+        let target = self.target.as_mut().ok_or("Nothing has been launched")?;
+        target.cpu = load_processor(&target.binary_path, &target.device_options);
+        target.call_depth = 0;
+        target.calls.reset();
+        if target.stop_on_entry {
+            self.running = None;
+            self.send_stopped("entry", None);
+        } else {
+            self.running = Some(RunMode::Continue);
+        }
+        Ok(Value::Null)
+    }
+
+    fn set_breakpoints(&mut self, arguments: &Value) -> Result<Value, String> {
+        let path = arguments["source"]["path"].as_str().ok_or("Breakpoints need a source path")?;
+        let path = std::fs::canonicalize(path).unwrap_or(PathBuf::from(path));
+        let lines = arguments["breakpoints"].as_array().map(|breakpoints| {
+            breakpoints.iter().filter_map(|breakpoint| breakpoint["line"].as_u64()).map(|line| line as u32).collect()
+        }).unwrap_or_default();
+        self.requested_breakpoints.insert(path.clone(), lines);
+        let lines = self.requested_breakpoints[&path].clone();
+        Ok(json!({ "breakpoints": self.resolve_breakpoints(&path, &lines) }))
+    }
+
+    /// replaces the breakpoints in a file, returns them in the format the client wants
+    fn resolve_breakpoints(&mut self, path: &Path, lines: &[u32]) -> Vec<Value> {
+        let Some(target) = &self.target else {
+            return lines.iter().map(|line| json!({ "verified": false, "line": line })).collect();
+        };
+        let file = target.files.iter().position(|(_, file_path)| file_path == path);
+        // the old breakpoints in this file are removed first
+        if let Some(file) = file {
+            let old = target.symbol_table.lines.iter()
+                .filter(|(_, line)| line.file == target.files[file].0)
+                .map(|(address, _)| *address)
+                .collect::<Vec<u16>>();
+            for address in old {
+                self.breakpoints.remove(&address);
+            }
+        }
+
+        let mut results = Vec::with_capacity(lines.len());
+        for line in lines {
+            let id = self.next_breakpoint_id;
+            self.next_breakpoint_id += 1;
+            // lines without code (comments, labels) move down to the next line that has some
+            let found = file.and_then(|file| {
+                let file_name = &target.files[file].0;
+                let last_line = target.symbol_table.lines.values()
+                    .filter(|source_line| &source_line.file == file_name)
+                    .map(|source_line| source_line.line)
+                    .max()?;
+                (*line..=last_line).find_map(|candidate| {
+                    let addresses = target.symbol_table.addresses_of_line(candidate, |other| other == file_name);
+                    addresses.first().map(|address| (candidate, *address))
+                })
+            });
+            match found {
+                Some((actual_line, address)) => {
+                    self.breakpoints.insert(address, id);
+                    results.push(json!({ "id": id, "verified": true, "line": actual_line }));
+                }
+                None => results.push(json!({
+                    "id": id,
+                    "verified": false,
+                    "line": line,
+                    "message": "No code on or after this line",
+                })),
+            }
+        }
+        results
+    }
+
+    fn resume_with(&mut self, mode: Option<RunMode>) -> Result<Value, String> {
+        let mode = mode.ok_or("Nothing has been launched")?;
+        self.running = Some(mode);
+        Ok(Value::Null)
+    }
+
+    /// runs the program for a bit, stopping if a breakpoint or the end of a step is reached
+    fn run_chunk(&mut self) {
+        let Some(mode) = self.running else {
+            return;
+        };
+        let Some(target) = &mut self.target else {
+            self.running = None;
+            return;
+        };
+        let mut stop = None;
+        let mut faults = Vec::new();
+        for _ in 0..RUN_CHUNK {
+            target.step();
+            if let Some(fault) = &target.cpu.fault {
+                faults.push(fault.report(&target.symbol_table));
+                if fault.halted() {
+                    stop = Some(("exception", None));
+                    break;
+                }
+            }
+            let program_counter = target.cpu.program_counter;
+            if let Some(id) = self.breakpoints.get(&program_counter) {
+                stop = Some(("breakpoint", Some(*id)));
+                break;
+            }
+            if (END_LOOP_START..=END_LOOP_END).contains(&program_counter) {
+                stop = Some(("exited", None));
+                break;
+            }
+            let line = target.current_line();
+            let at_line_start = target.symbol_table.lines.contains_key(&program_counter);
+            let stepped = match mode {
+                RunMode::Continue => false,
+                // no line info means we're stepping by instruction
+                RunMode::StepIn { from_line } => line.is_none() || (at_line_start && line != from_line),
+                RunMode::StepOver { from_line, call_depth } => target.call_depth <= call_depth
+                    && (line.is_none() || (at_line_start && line != from_line)),
+                RunMode::StepOut { call_depth } => target.call_depth < call_depth,
+            };
+            if stepped {
+                stop = Some(("step", None));
+                break;
+            }
+        }
+        let output = target.take_serial_output();
+        if !output.is_empty() {
+            self.send_output("stdout", &String::from_utf8_lossy(&output));
+        }
+        for fault in faults.iter() {
+            self.send_output("stderr", &format!("{fault}\n"));
+        }
+        if let Some((reason, breakpoint)) = stop {
+            self.running = None;
+            if reason == "exited" {
+                self.send_output("console", "Program finished (reached the end loop)\n");
+                self.send_event("exited", json!({ "exitCode": 0 }));
+                self.send_event("terminated", Value::Null);
+            } else {
+                self.send_stopped(reason, breakpoint);
+            }
+        }
+    }
+
+    fn stack_trace(&self) -> Result<Value, String> {
+        let target = self.target.as_ref().ok_or("Nothing has been launched")?;
+        let calls = call_stack::reconstruct(&target.cpu);
+        let mut frames = Vec::with_capacity(calls.len() + 1);
+        // each frame is in the subroutine called by the next one out
+        let mut locations = vec![target.cpu.program_counter];
+        locations.extend(calls.iter().map(|call| call.call_address));
+        for (index, address) in locations.iter().enumerate() {
+            let name = match calls.get(index) {
+                Some(call) => call_stack::subroutine_name(&target.symbol_table, call.target),
+                None => "main".to_string(),
+            };
+            let mut frame = json!({
+                "id": index,
+                "name": name,
+                "line": 0,
+                "column": 0,
+                "instructionPointerReference": format!("0x{address:04x}"),
+            });
+            if let Some((file, line)) = target.line_at(*address) {
+                let (file_name, path) = &target.files[file];
+                frame["source"] = json!({ "name": file_name, "path": path });
+                frame["line"] = json!(line);
+                frame["column"] = json!(1);
+            }
+            frames.push(frame);
+        }
+        Ok(json!({ "stackFrames": frames, "totalFrames": frames.len() }))
+    }
+
+    fn variables(&self, reference: u64) -> Result<Value, String> {
+        let target = self.target.as_ref().ok_or("Nothing has been launched")?;
+        let cpu = &target.cpu;
+        let variables = match reference {
+            REGISTERS_REFERENCE => {
+                let mut variables = vec![variable("a", format_byte(cpu.accumulator))];
+                for (index, value) in cpu.registers.iter().enumerate() {
+                    variables.push(variable(&format!("r{index}"), format_byte(*value)));
+                }
+                variables.push(variable("sr", format!("0b{:08b}", cpu.status_register)));
+                variables.push(variable("sp", format_byte(cpu.stack_pointer)));
+                variables.push(variable("pc", format!("0x{:04x}", cpu.program_counter)));
+                variables.push(variable("cycles", cpu.cycles.to_string()));
+                variables.push(variable("instructions", cpu.instructions.to_string()));
+                variables
+            }
+            FLAGS_REFERENCE => FLAGS.iter()
+                .map(|(name, mask)| variable(name, format_flag(cpu.status_register, *mask)))
+                .collect(),
+            DEFINES_REFERENCE => {
+                let mut defines = target.symbol_table.defines.values().collect::<Vec<_>>();
+                defines.sort_by(|a, b| a.name.cmp(&b.name));
+                defines.iter().map(|define| {
+                    let name = define.name.rsplit_once('/').unwrap_or(("", &define.name)).1;
+                    match target.symbol_table.resolve(name) {
+                        Some(address) => variable(name, format!("{} @ {address:04x}", format_byte(cpu.bus.peek(address)))),
+                        None => variable(name, define.value.clone()),
+                    }
+                }).collect()
+            }
+            _ => Vec::new(),
+        };
+        Ok(json!({ "variables": variables }))
+    }
+
+    /// registers, flags, or an address/symbol (which shows the byte there)
+    fn evaluate(&self, expression: &str) -> Result<Value, String> {
+        let target = self.target.as_ref().ok_or("Nothing has been launched")?;
+        let cpu = &target.cpu;
+        let expression = expression.trim();
+        let register = expression.to_lowercase().strip_prefix('r')
+            .and_then(|index| index.parse::<usize>().ok())
+            .filter(|index| *index < cpu.registers.len());
+        let flag = FLAGS.iter().find(|(name, _)| *name == expression.to_lowercase());
+        let result = if let Some(register) = register {
+            format_byte(cpu.registers[register])
+        } else if let Some((_, mask)) = flag {
+            format_flag(cpu.status_register, *mask)
+        } else {
+            match expression.to_lowercase().as_str() {
+                "a" | "acc" | "accumulator" => format_byte(cpu.accumulator),
+                "sr" | "status" => format!("0b{:08b}", cpu.status_register),
+                "sp" => format_byte(cpu.stack_pointer),
+                "pc" => format!("0x{:04x}", cpu.program_counter),
+                _ => {
+                    let address = memory_view::parse_address(&target.symbol_table, expression)?;
+                    format!("{} @ {address:04x}", format_byte(cpu.bus.peek(address)))
+                }
+            }
+        };
+        Ok(json!({ "result": result, "variablesReference": 0 }))
+    }
+
+    // ------------------------------ MESSAGES ------------------------------
+    fn send_response(&mut self, request: &Value, result: Result<Value, String>) {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": result.is_ok(),
+        });
+        match result {
+            Ok(Value::Null) => {}
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = json!(message),
+        }
+        self.send(response);
+    }
+    fn send_event(&mut self, event: &str, body: Value) {
+        let mut message = json!({ "type": "event", "event": event });
+        if !body.is_null() {
+            message["body"] = body;
+        }
+        self.send(message);
+    }
+    fn send_stopped(&mut self, reason: &str, breakpoint: Option<u64>) {
+        let mut body = json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true });
+        if let Some(breakpoint) = breakpoint {
+            body["hitBreakpointIds"] = json!([breakpoint]);
+        }
+        self.send_event("stopped", body);
+    }
+    fn send_output(&mut self, category: &str, text: &str) {
+        self.send_event("output", json!({ "category": category, "output": text }));
+    }
+    fn send(&mut self, mut message: Value) {
+        message["seq"] = json!(self.seq);
+        self.seq += 1;
+        let content = message.to_string();
+        let mut stdout = std::io::stdout().lock();
+        write!(stdout, "Content-Length: {}\r\n\r\n{content}", content.len()).expect("Failed to write to the debug client!");
+        stdout.flush().expect("Failed to write to the debug client!");
+    }
+}
+
+impl Target {
+    fn step(&mut self) {
+        let opcode = self.cpu.bus.memory[self.cpu.program_counter as usize];
+        self.cpu.step();
+        match self.cpu.fault.as_ref().map(|fault| fault.outcome) {
+            // the instruction didn't run
+            Some(FaultOutcome::Halted) => {}
+            Some(FaultOutcome::Reset) => {
+                self.call_depth = 0;
+                self.calls.reset();
+            }
+            _ => {
+                for change in self.calls.step(opcode, &self.cpu).into_iter().flatten() {
+                    match change {
+                        CallChange::Call(_) => self.call_depth += 1,
+                        CallChange::Return => self.call_depth -= 1,
+                    }
+                }
+            }
+        }
+    }
+    fn take_serial_output(&mut self) -> Vec<u8> {
+        let serial = self.cpu.bus.device_mut::<SerialPort>().expect("The serial port should always be on the bus");
+        serial.take_output()
+    }
+    fn current_line(&self) -> Option<(usize, u32)> {
+        self.line_at(self.cpu.program_counter)
+    }
+    /// the line an address is on, as (index into files, line)
+    fn line_at(&self, address: u16) -> Option<(usize, u32)> {
+        // only the first instruction of each line is in the table (a jsr is two instructions)
+        let source_line = (0..=4u16)
+            .filter_map(|offset| address.checked_sub(offset))
+            .find_map(|address| self.symbol_table.lines.get(&address))?;
+        let file = self.files.iter().position(|(file, _)| *file == source_line.file)?;
+        Some((file, source_line.line))
+    }
+}
+
+fn variable(name: &str, value: String) -> Value {
+    json!({ "name": name, "value": value, "variablesReference": 0 })
+}
+
+fn format_flag(status_register: u8, mask: u8) -> String {
+    if status_register & mask != 0 { "1".to_string() } else { "0".to_string() }
+}
+
+fn format_byte(value: u8) -> String {
+    format!("0x{value:02x} ({value})")
+}
@@ -0,0 +1,86 @@
+use std::fmt;
+use crate::simulator::bin_parser;
+use crate::simulator::bin_parser::{Instruction, ParseError, MAX_INSTRUCTION_LENGTH};
+
+/// instructions that have already been decoded, by address, so running the same code again skips the decoder
+/// anything that writes to memory has to invalidate the addresses it wrote (Processor::step does this for the bus)
+pub struct DecodeCache {
+    entries: Box<[Option<(Instruction, u8)>]>,
+    pub enabled: bool,
+    pub hits: u64,
+    pub misses: u64,
+}
+impl Default for DecodeCache {
+    fn default() -> Self {
+        DecodeCache::new(true)
+    }
+}
+impl fmt::Debug for DecodeCache {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // the entries are far too long to print
+        formatter.debug_struct("DecodeCache")
+            .field("enabled", &self.enabled)
+            .field("hits", &self.hits)
+            .field("misses", &self.misses)
+            .finish()
+    }
+}
+impl DecodeCache {
+    pub fn new(enabled: bool) -> DecodeCache {
+        DecodeCache {
+            entries: vec![None; 65536].into_boxed_slice(),
+            enabled,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// the instruction at the address and its number of operand bytes, like bin_parser::parse_instruction
+    /// (invalid instructions aren't cached, since they reset or stop the cpu anyway)
+    pub fn decode(&mut self, memory: &[u8; 65536], program_counter: u16) -> Result<(Instruction, u8), ParseError> {
+        if !self.enabled {
+            return bin_parser::parse_instruction(memory, program_counter);
+        }
+        if let Some(decoded) = self.entries[program_counter as usize] {
+            self.hits += 1;
+            return Ok(decoded);
+        }
+        self.misses += 1;
+        let decoded = bin_parser::parse_instruction(memory, program_counter)?;
+        self.entries[program_counter as usize] = Some(decoded);
+        Ok(decoded)
+    }
+
+    /// forgets every instruction that the byte at the address could be part of
+    pub fn invalidate(&mut self, address: u16) {
+        for offset in 0..MAX_INSTRUCTION_LENGTH {
+            self.entries[address.wrapping_sub(offset) as usize] = None;
+        }
+    }
+    pub fn clear(&mut self) {
+        self.entries.fill(None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulator::executor::Processor;
+
+    #[test]
+    fn writing_the_last_byte_of_an_instruction_invalidates_it() {
+        let mut cpu = Processor::new();
+        // beq 00, 0300 indexed by r1 (the index register is the fifth byte)
+        cpu.bus.memory[0x0200..0x0205].copy_from_slice(&[0x4B, 0x00, 0x03, 0x00, 0x01]);
+        cpu.registers[1] = 0x10;
+        cpu.registers[2] = 0x20;
+        cpu.program_counter = 0x0200;
+        cpu.step();
+        assert_eq!(cpu.program_counter, 0x0310);
+
+        // index by r2 instead
+        cpu.bus.write(0x0204, 0x02);
+        cpu.program_counter = 0x0200;
+        cpu.step();
+        assert_eq!(cpu.program_counter, 0x0320);
+    }
+}
@@ -0,0 +1,58 @@
+pub mod bridge;
+pub mod framebuffer;
+pub mod gpio;
+pub mod lcd;
+pub mod rng;
+pub mod serial;
+pub mod seven_segment;
+pub mod timer;
+
+use crate::simulator::bus::Bus;
+use crate::simulator::devices::gpio::GpioOptions;
+use crate::simulator::devices::serial::SerialOptions;
+use crate::simulator::fault::{OpcodePolicy, StackPolicy};
+use crate::simulator::timing::CycleTable;
+
+pub const FRAMEBUFFER_START: u16 = 0xE000;
+pub const FRAMEBUFFER_END: u16 = 0xE7FF;
+pub const SERIAL_START: u16 = 0xFF00;
+pub const SERIAL_END: u16 = 0xFF0F;
+pub const TIMER_START: u16 = 0xFF10;
+pub const TIMER_END: u16 = 0xFF17;
+pub const GPIO_START: u16 = 0xFF18;
+pub const GPIO_END: u16 = 0xFF1F;
+pub const SEVEN_SEGMENT_START: u16 = 0xFF20;
+pub const SEVEN_SEGMENT_END: u16 = 0xFF23;
+pub const LCD_START: u16 = 0xFF24;
+pub const LCD_END: u16 = 0xFF27;
+pub const RNG_START: u16 = 0xFF28;
+pub const RNG_END: u16 = 0xFF2B;
+
+/// settings for the devices that need to survive the cpu being reset
+#[derive(Debug, Default, Clone)]
+pub struct DeviceOptions {
+    pub serial: SerialOptions,
+    pub gpio: GpioOptions,
+    /// the rng is repeatable with a seed, and truly random without one
+    pub rng_seed: Option<u16>,
+    /// the framebuffer takes over ram, so it's only there when it's asked for
+    pub framebuffer: bool,
+    /// not a device, but it decides when the devices are ticked
+    pub timing: CycleTable,
+    /// also not devices, but they have to be set on every new cpu
+    pub stack_policy: StackPolicy,
+    pub opcode_policy: OpcodePolicy,
+}
+
+/// maps the devices that GoldCore has onto the bus
+pub fn register_defaults(bus: &mut Bus, options: &DeviceOptions) {
+    bus.register(SERIAL_START, SERIAL_END, serial::SerialPort::new(options.serial.clone()));
+    bus.register(TIMER_START, TIMER_END, timer::Timer::new());
+    bus.register(GPIO_START, GPIO_END, gpio::Gpio::new(options.gpio.clone()));
+    bus.register(SEVEN_SEGMENT_START, SEVEN_SEGMENT_END, seven_segment::SevenSegment::new());
+    bus.register(LCD_START, LCD_END, lcd::Lcd::new());
+    bus.register(RNG_START, RNG_END, rng::Rng::new(options.rng_seed));
+    if options.framebuffer {
+        bus.register(FRAMEBUFFER_START, FRAMEBUFFER_END, framebuffer::Framebuffer::new());
+    }
+}
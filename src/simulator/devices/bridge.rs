@@ -0,0 +1,200 @@
+use std::fmt::Debug;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+/// how many bytes a bridge holds for a host that isn't keeping up before it drops them
+pub const SEND_BUFFER_SIZE: usize = 4096;
+
+/// connects the simulated serial port to something on the host
+pub trait Bridge: Debug {
+    /// reads up to max bytes that the host has sent, without blocking
+    fn receive(&mut self, max: usize) -> Vec<u8>;
+    /// queues bytes for the host and sends what it can without blocking, dropping anything past SEND_BUFFER_SIZE
+    fn send(&mut self, bytes: &[u8]);
+    /// sends what it can of the queue without blocking, called on every tick so the last bytes aren't left behind
+    fn flush(&mut self);
+    /// true while there are queued bytes the host hasn't taken yet
+    fn is_backed_up(&self) -> bool;
+    /// where the host should connect to
+    fn description(&self) -> String;
+}
+
+fn queue(unsent: &mut Vec<u8>, bytes: &[u8]) {
+    let room = SEND_BUFFER_SIZE.saturating_sub(unsent.len());
+    unsent.extend_from_slice(&bytes[..bytes.len().min(room)]);
+}
+
+/// writes as much of unsent as the host will take, stopping without an error when it isn't ready for more
+fn write_unsent(writer: &mut impl Write, unsent: &mut Vec<u8>) -> std::io::Result<()> {
+    while !unsent.is_empty() {
+        match writer.write(unsent) {
+            Ok(0) => return Err(ErrorKind::WriteZero.into()),
+            Ok(bytes_written) => {
+                unsent.drain(..bytes_written);
+            }
+            Err(error) if matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut | ErrorKind::Interrupted) => break,
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BridgeConfig {
+    Pty,
+    Tcp(u16),
+}
+impl BridgeConfig {
+    pub fn open(self) -> Box<dyn Bridge> {
+        match self {
+            BridgeConfig::Pty => open_pty(),
+            BridgeConfig::Tcp(port) => Box::new(TcpBridge::new(port)),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn open_pty() -> Box<dyn Bridge> {
+    Box::new(PtyBridge::new())
+}
+#[cfg(not(unix))]
+fn open_pty() -> Box<dyn Bridge> {
+    panic!("Serial PTYs are only supported on linux, use --serial-tcp instead!");
+}
+
+#[cfg(unix)]
+#[derive(Debug)]
+pub struct PtyBridge {
+    master: serialport::TTYPort,
+    // held open so reads from the master don't fail when nothing is attached
+    slave: serialport::TTYPort,
+    // bytes the other end wasn't ready for yet
+    unsent: Vec<u8>,
+}
+#[cfg(unix)]
+impl PtyBridge {
+    pub fn new() -> PtyBridge {
+        use serialport::SerialPort;
+        let (mut master, slave) = serialport::TTYPort::pair().expect("Failed to create a PTY for the serial port!");
+        // writes give up straight away when the pty is full, and are tried again on the next tick
+        master.set_timeout(std::time::Duration::ZERO).expect("Failed to set the PTY timeout!");
+        PtyBridge { master, slave, unsent: Vec::new() }
+    }
+}
+#[cfg(unix)]
+impl Bridge for PtyBridge {
+    fn receive(&mut self, max: usize) -> Vec<u8> {
+        use serialport::SerialPort;
+        let available = self.master.bytes_to_read().unwrap_or(0) as usize;
+        let mut buffer = vec![0; available.min(max)];
+        if buffer.is_empty() {
+            return buffer;
+        }
+        let bytes_read = self.master.read(&mut buffer).unwrap_or(0);
+        buffer.truncate(bytes_read);
+        buffer
+    }
+    fn send(&mut self, bytes: &[u8]) {
+        queue(&mut self.unsent, bytes);
+        self.flush();
+    }
+    fn flush(&mut self) {
+        if !self.unsent.is_empty() && write_unsent(&mut self.master, &mut self.unsent).is_err() {
+            self.unsent.clear();
+        }
+    }
+    fn is_backed_up(&self) -> bool {
+        !self.unsent.is_empty()
+    }
+    fn description(&self) -> String {
+        use serialport::SerialPort;
+        self.slave.name().unwrap_or("unknown pty".to_string())
+    }
+}
+
+/// listens on localhost, one client can be connected at a time
+#[derive(Debug)]
+pub struct TcpBridge {
+    listener: TcpListener,
+    client: Option<TcpStream>,
+    // bytes the client wasn't ready for yet
+    unsent: Vec<u8>,
+}
+impl TcpBridge {
+    pub fn new(port: u16) -> TcpBridge {
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .unwrap_or_else(|error| panic!("Could not listen on port {port} for the serial port ({error})!"));
+        listener.set_nonblocking(true).expect("Failed to make the serial listener non-blocking!");
+        TcpBridge {
+            listener,
+            client: None,
+            unsent: Vec::new(),
+        }
+    }
+    fn accept(&mut self) {
+        if self.client.is_none()
+            && let Ok((client, _)) = self.listener.accept()
+        {
+            client.set_nonblocking(true).expect("Failed to make the serial client non-blocking!");
+            let _ = client.set_nodelay(true);
+            self.client = Some(client);
+        }
+    }
+}
+impl Bridge for TcpBridge {
+    fn receive(&mut self, max: usize) -> Vec<u8> {
+        self.accept();
+        let mut buffer = vec![0; max];
+        let Some(client) = &mut self.client else {
+            return Vec::new();
+        };
+        match client.read(&mut buffer) {
+            Ok(0) => {
+                // disconnected
+                self.client = None;
+                buffer.clear();
+            }
+            Ok(bytes_read) => buffer.truncate(bytes_read),
+            Err(error) => {
+                if error.kind() != ErrorKind::WouldBlock {
+                    self.client = None;
+                }
+                buffer.clear();
+            }
+        }
+        buffer
+    }
+    fn send(&mut self, bytes: &[u8]) {
+        self.accept();
+        if self.client.is_none() {
+            // nobody is listening, so the data is lost like it would be on a real wire
+            return;
+        }
+        queue(&mut self.unsent, bytes);
+        self.flush();
+    }
+    fn flush(&mut self) {
+        if self.unsent.is_empty() {
+            return;
+        }
+        let Some(client) = &mut self.client else {
+            self.unsent.clear();
+            return;
+        };
+        if write_unsent(client, &mut self.unsent).is_err() {
+            self.client = None;
+            self.unsent.clear();
+        }
+    }
+    fn is_backed_up(&self) -> bool {
+        !self.unsent.is_empty()
+    }
+    fn description(&self) -> String {
+        let address = self.listener.local_addr().map(|address| address.to_string()).unwrap_or_default();
+        if self.client.is_some() {
+            format!("tcp {address}, connected")
+        } else {
+            format!("tcp {address}")
+        }
+    }
+}
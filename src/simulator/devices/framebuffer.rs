@@ -0,0 +1,63 @@
+use crate::simulator::bus::Device;
+
+pub const COLUMNS: usize = 40;
+pub const ROWS: usize = 25;
+const CELLS: usize = COLUMNS * ROWS;
+/// the attributes start on the next 1k boundary after the characters
+const ATTRIBUTES: u16 = 0x0400;
+/// light gray on black, so text shows up without setting any attributes
+pub const DEFAULT_ATTRIBUTE: u8 = 0x07;
+
+/// a text mode screen at E000-E7FF, like the vga text controller will have
+/// each cell has a character (code page 437) and an attribute, with the foreground color in the low nibble and the
+/// background in the high nibble
+#[derive(Debug)]
+pub struct Framebuffer {
+    characters: [u8; CELLS],
+    attributes: [u8; CELLS],
+}
+impl Default for Framebuffer {
+    fn default() -> Self {
+        Framebuffer {
+            characters: [b' '; CELLS],
+            attributes: [DEFAULT_ATTRIBUTE; CELLS],
+        }
+    }
+}
+impl Framebuffer {
+    pub fn new() -> Framebuffer {
+        Framebuffer::default()
+    }
+    /// the character and attribute at a position on the screen
+    pub fn cell(&self, row: usize, column: usize) -> (u8, u8) {
+        let index = row * COLUMNS + column;
+        (self.characters[index], self.attributes[index])
+    }
+}
+impl Device for Framebuffer {
+    fn read(&mut self, offset: u16) -> u8 {
+        self.peek(offset)
+    }
+    fn write(&mut self, offset: u16, value: u8) {
+        let offset = offset as usize;
+        if offset < CELLS {
+            self.characters[offset] = value;
+        } else if let Some(index) = offset.checked_sub(ATTRIBUTES as usize).filter(|index| *index < CELLS) {
+            self.attributes[index] = value;
+        }
+        // the gaps after each plane aren't connected to anything
+    }
+    fn peek(&self, offset: u16) -> u8 {
+        let offset = offset as usize;
+        if offset < CELLS {
+            self.characters[offset]
+        } else if let Some(index) = offset.checked_sub(ATTRIBUTES as usize).filter(|index| *index < CELLS) {
+            self.attributes[index]
+        } else {
+            0
+        }
+    }
+    fn reset(&mut self) {
+        *self = Framebuffer::new();
+    }
+}
@@ -0,0 +1,203 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::rc::Rc;
+use crate::simulator::bus::Device;
+
+// register offsets (see doc/GoldCore.md)
+const BOARD_LEDS: u16 = 0x00;
+const IO_LEDS: u16 = 0x01; // to 0x03
+const SWITCHES: u16 = 0x04; // to 0x06
+const BUTTONS: u16 = 0x07;
+
+pub const IO_LED_COUNT: usize = 24;
+pub const SWITCH_COUNT: usize = 24;
+/// the buttons on the io shield, in the order of their bits in the buttons register
+pub const BUTTON_NAMES: [&str; 5] = ["up", "center", "down", "left", "right"];
+
+pub type SharedGpioLog = Rc<RefCell<GpioLog>>;
+
+/// the switches and buttons, which are on the board so they stay how they are when the cpu is reset
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct GpioInputs {
+    /// switch n is bit n
+    pub switches: u32,
+    /// button n (see BUTTON_NAMES) is bit n, set while it's held down
+    pub buttons: u8,
+}
+impl GpioInputs {
+    pub fn toggle_switch(&mut self, switch: usize) {
+        self.switches ^= 1 << switch;
+    }
+    pub fn toggle_button(&mut self, button: usize) {
+        self.buttons ^= 1 << button;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GpioAction {
+    Switch(usize, bool),
+    /// the button is held until it's released
+    Press(usize),
+    Release(usize),
+}
+
+/// something a script does to the inputs once the cpu has run for the given number of cycles
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GpioEvent {
+    pub cycle: u64,
+    pub action: GpioAction,
+}
+
+/// reads a script with one event per line, like `1000 switch 3 on`, `2000 press center` or `2500 release center`
+/// (cycles are counted from the last reset, and anything after a # is a comment)
+pub fn parse_script(text: &str) -> Result<Vec<GpioEvent>, String> {
+    let mut events = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap();
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.is_empty() {
+            continue;
+        }
+        let event = parse_event(&words).map_err(|error| format!("Line {} of the gpio script: {error}", index + 1))?;
+        events.push(event);
+    }
+    // events happen in order of their cycles, not of their lines
+    events.sort_by_key(|event| event.cycle);
+    Ok(events)
+}
+
+fn parse_event(words: &[&str]) -> Result<GpioEvent, String> {
+    let cycle = words[0].parse::<u64>().map_err(|_| format!("\"{}\" isn't a cycle count", words[0]))?;
+    let button = |name: Option<&&str>| -> Result<usize, String> {
+        let name = name.ok_or("expected a button name")?;
+        BUTTON_NAMES.iter().position(|button| button == name)
+            .ok_or(format!("there is no button called \"{name}\" (the buttons are {})", BUTTON_NAMES.join(", ")))
+    };
+    let action = match words.get(1).copied() {
+        Some("switch") => {
+            let switch = words.get(2).and_then(|switch| switch.parse::<usize>().ok())
+                .filter(|switch| *switch < SWITCH_COUNT)
+                .ok_or(format!("expected a switch number from 0 to {}", SWITCH_COUNT - 1))?;
+            let on = match words.get(3).copied() {
+                Some("on") => true,
+                Some("off") => false,
+                _ => return Err("expected on or off after the switch number".to_string()),
+            };
+            GpioAction::Switch(switch, on)
+        }
+        Some("press") => GpioAction::Press(button(words.get(2))?),
+        Some("release") => GpioAction::Release(button(words.get(2))?),
+        _ => return Err("expected switch, press or release after the cycle count".to_string()),
+    };
+    Ok(GpioEvent { cycle, action })
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct GpioOptions {
+    /// events from a script, which start over on every reset
+    pub script: Vec<GpioEvent>,
+    pub log: Option<SharedGpioLog>,
+}
+
+/// records the leds every time the program changes them, one line per change
+#[derive(Debug)]
+pub struct GpioLog {
+    writer: BufWriter<File>,
+}
+impl GpioLog {
+    pub fn create(path: &str) -> GpioLog {
+        let file = File::create(path).unwrap_or_else(|error| panic!("Could not create gpio log \"{path}\" ({error})!"));
+        let mut log = GpioLog { writer: BufWriter::new(file) };
+        log.write_line("# cycle board_leds io_leds");
+        log
+    }
+    fn record(&mut self, cycle: u64, board_leds: u8, io_leds: u32) {
+        self.write_line(&format!("{cycle} {board_leds:08b} {io_leds:024b}"));
+    }
+    fn write_line(&mut self, line: &str) {
+        writeln!(self.writer, "{line}").expect("Failed to write to the gpio log!");
+        // flushed every line so the log is complete even if the simulator is killed
+        self.writer.flush().expect("Failed to write to the gpio log!");
+    }
+}
+
+/// the leds, switches and buttons on the board and io shield at FF18-FF1F
+#[derive(Debug, Default)]
+pub struct Gpio {
+    /// led n is bit n
+    pub board_leds: u8,
+    pub io_leds: u32,
+    pub inputs: GpioInputs,
+    script: VecDeque<GpioEvent>,
+    /// the cycle of the last tick, which writes are logged at
+    cycle: u64,
+    pub options: GpioOptions,
+}
+impl Gpio {
+    pub fn new(options: GpioOptions) -> Gpio {
+        Gpio {
+            script: options.script.iter().copied().collect(),
+            options,
+            ..Gpio::default()
+        }
+    }
+    fn apply(&mut self, action: GpioAction) {
+        match action {
+            GpioAction::Switch(switch, true) => self.inputs.switches |= 1 << switch,
+            GpioAction::Switch(switch, false) => self.inputs.switches &= !(1 << switch),
+            GpioAction::Press(button) => self.inputs.buttons |= 1 << button,
+            GpioAction::Release(button) => self.inputs.buttons &= !(1 << button),
+        }
+    }
+}
+impl Device for Gpio {
+    fn read(&mut self, offset: u16) -> u8 {
+        self.peek(offset)
+    }
+    fn write(&mut self, offset: u16, value: u8) {
+        let leds = (self.board_leds, self.io_leds);
+        match offset {
+            BOARD_LEDS => self.board_leds = value,
+            IO_LEDS..=0x03 => {
+                let shift = (offset - IO_LEDS) * 8;
+                self.io_leds = (self.io_leds & !(0xFF << shift)) | ((value as u32) << shift);
+            }
+            // the switches and buttons are read only
+            _ => {}
+        }
+        if let Some(log) = &self.options.log
+            && leds != (self.board_leds, self.io_leds)
+        {
+            log.borrow_mut().record(self.cycle, self.board_leds, self.io_leds);
+        }
+    }
+    fn peek(&self, offset: u16) -> u8 {
+        match offset {
+            BOARD_LEDS => self.board_leds,
+            IO_LEDS..=0x03 => (self.io_leds >> ((offset - IO_LEDS) * 8)) as u8,
+            SWITCHES..=0x06 => (self.inputs.switches >> ((offset - SWITCHES) * 8)) as u8,
+            BUTTONS => self.inputs.buttons,
+            _ => 0,
+        }
+    }
+    fn tick(&mut self, cycle: u64) {
+        self.cycle = cycle;
+        while let Some(event) = self.script.front()
+            && event.cycle <= cycle
+        {
+            let action = event.action;
+            self.script.pop_front();
+            self.apply(action);
+        }
+    }
+    fn reset(&mut self) {
+        if let Some(log) = &self.options.log {
+            log.borrow_mut().write_line("# reset");
+        }
+        let inputs = self.inputs;
+        *self = Gpio::new(self.options.clone());
+        self.inputs = inputs;
+    }
+}
@@ -0,0 +1,225 @@
+use crate::simulator::bus::Device;
+use crate::simulator::timing::CLOCK_HZ;
+
+// register offsets (see doc/GoldCore.md)
+const INSTRUCTION: u16 = 0x00;
+const DATA: u16 = 0x01;
+
+pub const COLUMNS: usize = 16;
+pub const ROWS: usize = 2;
+const DDRAM_SIZE: usize = 0x80;
+const CGRAM_SIZE: usize = 0x40;
+/// how many characters each line holds in two line mode, the second line starts at 40
+const LINE_LENGTH: u8 = 40;
+const SECOND_LINE: u8 = 0x40;
+
+/// most instructions take 37 µs
+const INSTRUCTION_CYCLES: u64 = CLOCK_HZ * 37 / 1_000_000;
+/// clear and return home take 1.52 ms
+const HOME_CYCLES: u64 = CLOCK_HZ * 1520 / 1_000_000;
+const BUSY_FLAG: u8 = 0b1000_0000;
+
+/// a 16x2 character lcd with an HD44780 controller at FF24-FF27, talking over the 8 bit interface
+/// the controller takes a while to carry out each instruction, and ignores anything written while it's busy
+#[derive(Debug)]
+pub struct Lcd {
+    /// the characters, one line at 00-27 and the other at 40-67
+    ddram: [u8; DDRAM_SIZE],
+    /// the 8 custom characters, 8 rows of 5 pixels each
+    cgram: [u8; CGRAM_SIZE],
+    address: u8,
+    /// whether the address points into cgram (after a set cgram address) instead of ddram
+    cgram_selected: bool,
+    increment: bool,
+    /// shift the display instead of moving the cursor when a character is written
+    shift_on_write: bool,
+    pub display_on: bool,
+    pub cursor_on: bool,
+    pub blink_on: bool,
+    pub two_lines: bool,
+    /// how far the display is shifted left
+    shift: u8,
+    busy_until: u64,
+    cycle: u64,
+}
+impl Default for Lcd {
+    fn default() -> Self {
+        // what the controller does to itself when it's powered on
+        Lcd {
+            ddram: [b' '; DDRAM_SIZE],
+            cgram: [0; CGRAM_SIZE],
+            address: 0,
+            cgram_selected: false,
+            increment: true,
+            shift_on_write: false,
+            display_on: false,
+            cursor_on: false,
+            blink_on: false,
+            two_lines: false,
+            shift: 0,
+            busy_until: 0,
+            cycle: 0,
+        }
+    }
+}
+impl Lcd {
+    pub fn new() -> Lcd {
+        Lcd::default()
+    }
+
+    /// the character shown at a position on the screen (codes 0-7 are the custom characters)
+    pub fn character_at(&self, row: usize, column: usize) -> u8 {
+        if !self.two_lines && row > 0 {
+            return b' ';
+        }
+        let line_length = if self.two_lines { LINE_LENGTH } else { LINE_LENGTH * 2 };
+        let offset = (column as u8 + self.shift) % line_length;
+        self.ddram[(row as u8 * SECOND_LINE + offset) as usize]
+    }
+    /// where the cursor is on the screen, if it's on a visible character
+    pub fn cursor_position(&self) -> Option<(usize, usize)> {
+        if self.cgram_selected {
+            return None;
+        }
+        let (row, offset) = if self.two_lines && self.address >= SECOND_LINE {
+            (1, self.address - SECOND_LINE)
+        } else {
+            (0, self.address)
+        };
+        let line_length = if self.two_lines { LINE_LENGTH } else { LINE_LENGTH * 2 };
+        let column = (offset + line_length - self.shift % line_length) % line_length;
+        (column < COLUMNS as u8).then_some((row, column as usize))
+    }
+
+    /// moves the address counter one step in the entry mode direction, wrapping the way the controller does
+    fn step_address(&mut self, forward: bool) {
+        if self.cgram_selected {
+            let address = if forward { self.address.wrapping_add(1) } else { self.address.wrapping_sub(1) };
+            self.address = address % CGRAM_SIZE as u8;
+            return;
+        }
+        self.address = match (self.two_lines, forward) {
+            (true, true) if self.address == LINE_LENGTH - 1 => SECOND_LINE,
+            (true, true) if self.address == SECOND_LINE + LINE_LENGTH - 1 => 0,
+            (true, false) if self.address == 0 => SECOND_LINE + LINE_LENGTH - 1,
+            (true, false) if self.address == SECOND_LINE => LINE_LENGTH - 1,
+            (false, true) if self.address == LINE_LENGTH * 2 - 1 => 0,
+            (false, false) if self.address == 0 => LINE_LENGTH * 2 - 1,
+            // addresses past the end of a line only come from setting the address directly
+            (_, true) => (self.address + 1) % DDRAM_SIZE as u8,
+            (_, false) => self.address - 1,
+        };
+    }
+    fn shift_display(&mut self, left: bool) {
+        let line_length = if self.two_lines { LINE_LENGTH } else { LINE_LENGTH * 2 };
+        self.shift = if left { (self.shift + 1) % line_length } else { (self.shift + line_length - 1) % line_length };
+    }
+
+    fn instruction(&mut self, value: u8) {
+        let mut cycles = INSTRUCTION_CYCLES;
+        match value.leading_zeros() {
+            // set ddram address
+            0 => {
+                self.address = value & 0x7F;
+                self.cgram_selected = false;
+            }
+            // set cgram address
+            1 => {
+                self.address = value & 0x3F;
+                self.cgram_selected = true;
+            }
+            // function set, only the number of lines matters since the interface is always 8 bits here
+            2 => self.two_lines = value & 0b0000_1000 != 0,
+            // cursor or display shift
+            3 => {
+                let right = value & 0b0000_0100 != 0;
+                if value & 0b0000_1000 != 0 {
+                    self.shift_display(!right);
+                } else {
+                    self.step_address(right);
+                }
+            }
+            // display on/off control
+            4 => {
+                self.display_on = value & 0b0000_0100 != 0;
+                self.cursor_on = value & 0b0000_0010 != 0;
+                self.blink_on = value & 0b0000_0001 != 0;
+            }
+            // entry mode set
+            5 => {
+                self.increment = value & 0b0000_0010 != 0;
+                self.shift_on_write = value & 0b0000_0001 != 0;
+            }
+            // return home
+            6 => {
+                self.address = 0;
+                self.cgram_selected = false;
+                self.shift = 0;
+                cycles = HOME_CYCLES;
+            }
+            // clear display
+            7 => {
+                self.ddram = [b' '; DDRAM_SIZE];
+                self.address = 0;
+                self.cgram_selected = false;
+                self.shift = 0;
+                self.increment = true;
+                cycles = HOME_CYCLES;
+            }
+            // 0 does nothing
+            _ => return,
+        }
+        self.busy_until = self.cycle + cycles;
+    }
+    fn write_data(&mut self, value: u8) {
+        if self.cgram_selected {
+            self.cgram[self.address as usize] = value & 0b0001_1111;
+        } else {
+            self.ddram[self.address as usize] = value;
+            if self.shift_on_write {
+                self.shift_display(self.increment);
+            }
+        }
+        self.step_address(self.increment);
+        self.busy_until = self.cycle + INSTRUCTION_CYCLES;
+    }
+    fn is_busy(&self) -> bool {
+        self.cycle < self.busy_until
+    }
+}
+impl Device for Lcd {
+    fn read(&mut self, offset: u16) -> u8 {
+        let value = self.peek(offset);
+        if offset == DATA && !self.is_busy() {
+            self.step_address(self.increment);
+            self.busy_until = self.cycle + INSTRUCTION_CYCLES;
+        }
+        value
+    }
+    fn write(&mut self, offset: u16, value: u8) {
+        if self.is_busy() {
+            return;
+        }
+        match offset {
+            INSTRUCTION => self.instruction(value),
+            DATA => self.write_data(value),
+            // unused
+            _ => {}
+        }
+    }
+    fn peek(&self, offset: u16) -> u8 {
+        match offset {
+            // the busy flag and the address counter
+            INSTRUCTION => (if self.is_busy() { BUSY_FLAG } else { 0 }) | self.address,
+            DATA if self.cgram_selected => self.cgram[self.address as usize],
+            DATA => self.ddram[self.address as usize],
+            _ => 0,
+        }
+    }
+    fn tick(&mut self, cycle: u64) {
+        self.cycle = cycle;
+    }
+    fn reset(&mut self) {
+        *self = Lcd::new();
+    }
+}
@@ -0,0 +1,65 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use crate::simulator::bus::Device;
+
+// register offsets (see doc/GoldCore.md)
+const RANDOM_BYTE: u16 = 0x00;
+
+/// taps for a 16 bit galois lfsr that goes through all 65535 nonzero states
+const TAPS: u16 = 0xB400;
+
+/// the random number generator at FF28-FF2B, reading the random byte register gives a new byte every time
+/// with a seed it's the lfsr the hardware will have, so runs can be repeated, and without one it's the host's randomness
+#[derive(Debug, Default)]
+pub struct Rng {
+    seed: Option<u16>,
+    lfsr: u16,
+    /// the byte the next read returns, so peeking doesn't use it up
+    next: u8,
+}
+impl Rng {
+    /// a seed of 0 starts from 1 instead, since an lfsr never leaves 0
+    pub fn new(seed: Option<u16>) -> Rng {
+        let mut rng = Rng { seed, lfsr: seed.unwrap_or(1).max(1), next: 0 };
+        rng.advance();
+        rng
+    }
+    fn advance(&mut self) {
+        self.next = match self.seed {
+            Some(_) => {
+                // a byte is 8 shifts, so consecutive bytes don't share bits
+                for _ in 0..8 {
+                    let bit = self.lfsr & 1;
+                    self.lfsr >>= 1;
+                    if bit != 0 {
+                        self.lfsr ^= TAPS;
+                    }
+                }
+                self.lfsr as u8
+            }
+            // every RandomState has new random keys, so hashing nothing gives a random number
+            None => RandomState::new().build_hasher().finish() as u8,
+        };
+    }
+}
+impl Device for Rng {
+    fn read(&mut self, offset: u16) -> u8 {
+        let value = self.peek(offset);
+        if offset == RANDOM_BYTE {
+            self.advance();
+        }
+        value
+    }
+    fn write(&mut self, _offset: u16, _value: u8) {
+        // read only
+    }
+    fn peek(&self, offset: u16) -> u8 {
+        match offset {
+            RANDOM_BYTE => self.next,
+            _ => 0,
+        }
+    }
+    fn reset(&mut self) {
+        *self = Rng::new(self.seed);
+    }
+}
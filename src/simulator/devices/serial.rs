@@ -0,0 +1,246 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::rc::Rc;
+use crate::simulator::bus::Device;
+use crate::simulator::devices::bridge::Bridge;
+
+// register offsets (see doc/GoldCore.md)
+const TX_BYTE: u16 = 0x00;
+const TX_NEW_DATA: u16 = 0x01;
+const TX_BLOCK: u16 = 0x02;
+const TX_BUSY: u16 = 0x03;
+const RX_BYTE: u16 = 0x08;
+const RX_NEW_DATA: u16 = 0x09;
+const RX_BUSY: u16 = 0x0A;
+const RX_INTERRUPT: u16 = 0x0B;
+
+pub const TX_FIFO_SIZE: usize = 16;
+/// how many bytes from the host we hold before we stop reading from the bridge
+pub const RX_BUFFER_SIZE: usize = 64;
+
+/// the bridge is shared so it survives the cpu being reset
+pub type SharedBridge = Rc<RefCell<Box<dyn Bridge>>>;
+pub type SharedLog = Rc<RefCell<SerialLog>>;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum FlowControl {
+    /// incoming bytes wait until the program has read the last one and the rx busy flag is clear,
+    /// and outgoing bytes wait while the block flag is set
+    #[default]
+    Busy,
+    /// the flags are ignored, so incoming bytes overwrite unread ones and the block flag does nothing
+    Off,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct SerialOptions {
+    pub flow_control: FlowControl,
+    pub bridge: Option<SharedBridge>,
+    /// bytes fed to the program from a file, starting over on every reset
+    pub scripted_input: Vec<u8>,
+    /// minimum number of cycles between scripted bytes
+    pub scripted_input_delay: u64,
+    /// how long one byte takes on the wire in clock cycles (clock / baud * 10), or 0 to send and receive instantly
+    pub cycles_per_byte: u64,
+    pub log: Option<SharedLog>,
+}
+
+/// records every byte that goes through the serial port, one per line
+/// (`cycle direction hex character`, where direction is tx or rx from the cpu's point of view)
+#[derive(Debug)]
+pub struct SerialLog {
+    writer: BufWriter<File>,
+}
+impl SerialLog {
+    pub fn create(path: &str) -> SerialLog {
+        let file = File::create(path).unwrap_or_else(|error| panic!("Could not create serial log \"{path}\" ({error})!"));
+        let mut log = SerialLog { writer: BufWriter::new(file) };
+        log.write_line("# cycle direction hex character");
+        log
+    }
+    fn record(&mut self, cycle: u64, direction: &str, byte: u8) {
+        let character = (byte as char).escape_default();
+        self.write_line(&format!("{cycle} {direction} {byte:02x} {character}"));
+    }
+    fn write_line(&mut self, line: &str) {
+        writeln!(self.writer, "{line}").expect("Failed to write to the serial log!");
+        // flushed every line so the log is complete even if the simulator is killed
+        self.writer.flush().expect("Failed to write to the serial log!");
+    }
+}
+
+/// the serial interface at FF00-FF0F
+/// "tx" and "rx" are from the cpu's point of view, so tx is what the program sends
+#[derive(Debug, Default)]
+pub struct SerialPort {
+    tx_byte: u8,
+    tx_block: u8,
+    tx_fifo: VecDeque<u8>,
+    rx_byte: u8,
+    rx_new_data: u8,
+    rx_busy: u8,
+    /// when set, the irq line is held while there's an unread byte
+    rx_interrupt: u8,
+    /// bytes waiting to be received by the program
+    pub input: VecDeque<u8>,
+    /// bytes the program has sent (that made it out of the fifo)
+    pub output: Vec<u8>,
+    scripted_input: VecDeque<u8>,
+    next_scripted_cycle: u64,
+    /// when the byte at the front of the fifo will have finished sending, if one is being sent
+    tx_done_cycle: Option<u64>,
+    /// the earliest the next byte can finish arriving
+    next_rx_cycle: u64,
+    pub options: SerialOptions,
+}
+impl SerialPort {
+    pub fn new(options: SerialOptions) -> SerialPort {
+        SerialPort {
+            scripted_input: options.scripted_input.iter().copied().collect(),
+            options,
+            ..SerialPort::default()
+        }
+    }
+    /// true if the program has told us to stop sending, or hasn't read the last byte yet
+    pub fn is_busy(&self) -> bool {
+        self.options.flow_control == FlowControl::Busy && (self.rx_new_data != 0 || self.rx_busy != 0)
+    }
+    /// true once every byte from the input file has been received by the program
+    pub fn scripted_input_done(&self) -> bool {
+        self.scripted_input.is_empty()
+    }
+    /// true while there are bytes in the fifo that haven't gone out yet, or that the bridge is still holding
+    pub fn is_sending(&self) -> bool {
+        !self.tx_fifo.is_empty() || self.bridge_backed_up()
+    }
+    fn bridge_backed_up(&self) -> bool {
+        self.options.bridge.as_ref().is_some_and(|bridge| bridge.borrow().is_backed_up())
+    }
+    pub fn take_output(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.output)
+    }
+    fn transmit(&mut self, cycle: u64) {
+        // this runs after every instruction, and there's usually nothing to send
+        if self.tx_fifo.is_empty() {
+            return;
+        }
+        // a host that isn't keeping up holds the bytes in the fifo, so the tx busy flag goes high once it fills
+        if self.options.flow_control == FlowControl::Busy && (self.tx_block != 0 || self.bridge_backed_up()) {
+            return;
+        }
+        let cycles_per_byte = self.options.cycles_per_byte;
+        let sent = if cycles_per_byte == 0 {
+            self.tx_fifo.drain(..).collect::<Vec<u8>>()
+        } else {
+            // bytes stay in the fifo while they're being sent, and more than one can finish in a long instruction
+            let mut sent = Vec::new();
+            loop {
+                match self.tx_done_cycle {
+                    Some(done_cycle) if cycle >= done_cycle => {
+                        sent.extend(self.tx_fifo.pop_front());
+                        self.tx_done_cycle = (!self.tx_fifo.is_empty()).then_some(done_cycle + cycles_per_byte);
+                    }
+                    None if !self.tx_fifo.is_empty() => {
+                        self.tx_done_cycle = Some(cycle + cycles_per_byte);
+                        break;
+                    }
+                    _ => break,
+                }
+            }
+            sent
+        };
+        if sent.is_empty() {
+            return;
+        }
+        if let Some(bridge) = &self.options.bridge {
+            bridge.borrow_mut().send(&sent);
+        }
+        if let Some(log) = &self.options.log {
+            for byte in sent.iter() {
+                log.borrow_mut().record(cycle, "tx", *byte);
+            }
+        }
+        self.output.extend(sent);
+    }
+    fn receive(&mut self, cycle: u64) {
+        if let Some(bridge) = &self.options.bridge
+            && self.input.len() < RX_BUFFER_SIZE
+        {
+            // not reading when the buffer is full pushes back on the host
+            let received = bridge.borrow_mut().receive(RX_BUFFER_SIZE - self.input.len());
+            self.input.extend(received);
+        }
+        if self.is_busy() || cycle < self.next_rx_cycle {
+            return;
+        }
+        // scripted input waits for anything typed in or sent over the bridge
+        let byte = if let Some(byte) = self.input.pop_front() {
+            byte
+        } else if cycle >= self.next_scripted_cycle
+            && let Some(byte) = self.scripted_input.pop_front()
+        {
+            self.next_scripted_cycle = cycle + self.options.scripted_input_delay;
+            byte
+        } else {
+            return;
+        };
+        self.rx_byte = byte;
+        self.rx_new_data = 1;
+        self.next_rx_cycle = cycle + self.options.cycles_per_byte;
+        if let Some(log) = &self.options.log {
+            log.borrow_mut().record(cycle, "rx", byte);
+        }
+    }
+}
+impl Device for SerialPort {
+    fn read(&mut self, offset: u16) -> u8 {
+        self.peek(offset)
+    }
+    fn write(&mut self, offset: u16, value: u8) {
+        match offset {
+            TX_BYTE => self.tx_byte = value,
+            // the byte goes into the fifo, which resets the flag (it's lost if the fifo is full)
+            TX_NEW_DATA if value != 0 && self.tx_fifo.len() < TX_FIFO_SIZE => self.tx_fifo.push_back(self.tx_byte),
+            TX_BLOCK => self.tx_block = value,
+            RX_BYTE => self.rx_byte = value,
+            RX_NEW_DATA => self.rx_new_data = value,
+            RX_BUSY => self.rx_busy = value,
+            RX_INTERRUPT => self.rx_interrupt = value,
+            // read only or unused
+            _ => {}
+        }
+    }
+    fn peek(&self, offset: u16) -> u8 {
+        match offset {
+            TX_BYTE => self.tx_byte,
+            TX_BLOCK => self.tx_block,
+            TX_BUSY => (self.tx_fifo.len() >= TX_FIFO_SIZE) as u8,
+            RX_BYTE => self.rx_byte,
+            RX_NEW_DATA => self.rx_new_data,
+            RX_BUSY => self.rx_busy,
+            RX_INTERRUPT => self.rx_interrupt,
+            _ => 0,
+        }
+    }
+    fn tick(&mut self, cycle: u64) {
+        if let Some(bridge) = &self.options.bridge {
+            bridge.borrow_mut().flush();
+        }
+        self.transmit(cycle);
+        self.receive(cycle);
+    }
+    fn reset(&mut self) {
+        if let Some(log) = &self.options.log {
+            log.borrow_mut().write_line("# reset");
+        }
+        *self = SerialPort::new(self.options.clone());
+    }
+    fn interrupt(&self) -> bool {
+        self.rx_interrupt != 0 && self.rx_new_data != 0
+    }
+    fn irq_connected(&self) -> bool {
+        true
+    }
+}
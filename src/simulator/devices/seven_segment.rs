@@ -0,0 +1,107 @@
+use crate::simulator::bus::Device;
+use crate::simulator::timing::CLOCK_HZ;
+
+// register offsets (see doc/GoldCore.md)
+const SEGMENTS: u16 = 0x00;
+const DIGIT_SELECT: u16 = 0x01;
+
+pub const DIGITS: usize = 4;
+/// a through g, then the decimal point
+pub const SEGMENT_COUNT: usize = 8;
+/// how long an eye holds on to a lit segment (20 ms), which is how often the brightness is worked out
+pub const PERSISTENCE_CYCLES: u64 = CLOCK_HZ / 50;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Brightness {
+    #[default]
+    Off,
+    /// lit for less than half of its share of the time, like a digit that's skipped over quickly while multiplexing
+    Dim,
+    Bright,
+}
+
+/// the four digit display on the io shield at FF20-FF23
+/// only one set of segments can be driven at a time, so programs show different digits by quickly switching which
+/// digits are selected, and a segment looks lit if it was on for long enough in the last persistence window
+#[derive(Debug, Default)]
+pub struct SevenSegment {
+    /// segment n is bit n (a = 0 through g = 6, decimal point = 7)
+    segments: u8,
+    /// digit n is bit n, with digit 0 on the right
+    digit_select: u8,
+    /// cycles each segment has been lit for in this window
+    lit_cycles: [[u64; SEGMENT_COUNT]; DIGITS],
+    window_start: u64,
+    last_cycle: u64,
+    /// how much of the last window each segment was lit for
+    last_window: [[f64; SEGMENT_COUNT]; DIGITS],
+}
+impl SevenSegment {
+    pub fn new() -> SevenSegment {
+        SevenSegment::default()
+    }
+    fn is_driven(&self, digit: usize, segment: usize) -> bool {
+        self.digit_select & (1 << digit) != 0 && self.segments & (1 << segment) != 0
+    }
+    /// how the segment looks: segments being driven right now are always lit, so it can be seen while stepping
+    pub fn brightness(&self, digit: usize, segment: usize) -> Brightness {
+        let share = self.last_window[digit][segment];
+        if self.is_driven(digit, segment) || share * DIGITS as f64 >= 0.5 {
+            Brightness::Bright
+        } else if share > 0.0 {
+            Brightness::Dim
+        } else {
+            Brightness::Off
+        }
+    }
+}
+impl Device for SevenSegment {
+    fn read(&mut self, offset: u16) -> u8 {
+        self.peek(offset)
+    }
+    fn write(&mut self, offset: u16, value: u8) {
+        match offset {
+            SEGMENTS => self.segments = value,
+            DIGIT_SELECT => self.digit_select = value & ((1 << DIGITS) - 1),
+            // unused
+            _ => {}
+        }
+    }
+    fn peek(&self, offset: u16) -> u8 {
+        match offset {
+            SEGMENTS => self.segments,
+            DIGIT_SELECT => self.digit_select,
+            _ => 0,
+        }
+    }
+    fn tick(&mut self, cycle: u64) {
+        // the registers were written during the instruction, so the whole instruction counts towards the new values
+        let elapsed = cycle - self.last_cycle;
+        self.last_cycle = cycle;
+        // this runs after every instruction, so it only looks at the set bits
+        let mut digits = self.digit_select;
+        while digits != 0 {
+            let digit = digits.trailing_zeros() as usize;
+            digits &= digits - 1;
+            let mut segments = self.segments;
+            while segments != 0 {
+                let segment = segments.trailing_zeros() as usize;
+                segments &= segments - 1;
+                self.lit_cycles[digit][segment] += elapsed;
+            }
+        }
+        let window = cycle - self.window_start;
+        if window >= PERSISTENCE_CYCLES {
+            for digit in 0..DIGITS {
+                for segment in 0..SEGMENT_COUNT {
+                    self.last_window[digit][segment] = self.lit_cycles[digit][segment] as f64 / window as f64;
+                }
+            }
+            self.lit_cycles = Default::default();
+            self.window_start = cycle;
+        }
+    }
+    fn reset(&mut self) {
+        *self = SevenSegment::new();
+    }
+}
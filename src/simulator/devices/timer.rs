@@ -0,0 +1,116 @@
+use crate::simulator::bus::Device;
+
+// register offsets (see doc/GoldCore.md)
+const COUNTER_HIGH: u16 = 0x00;
+const COUNTER_LOW: u16 = 0x01;
+const RELOAD_HIGH: u16 = 0x02;
+const RELOAD_LOW: u16 = 0x03;
+const PRESCALER: u16 = 0x04;
+const CONTROL: u16 = 0x05;
+const OVERFLOW: u16 = 0x06;
+
+// control register bits
+const ENABLE: u8 = 0b0000_0001;
+const INTERRUPT_ENABLE: u8 = 0b0000_0010;
+/// stop at the first overflow instead of reloading
+const ONE_SHOT: u8 = 0b0000_0100;
+
+/// the timer at FF10-FF17
+/// the counter counts down once every prescaler + 1 cycles, and when it would go below zero it's reloaded and the
+/// overflow flag is set, so it overflows every (reload + 1) * (prescaler + 1) cycles
+#[derive(Debug, Default)]
+pub struct Timer {
+    counter: u16,
+    reload: u16,
+    prescaler: u8,
+    control: u8,
+    overflow: u8,
+    /// the low byte of the counter from when the high byte was read, so reading both bytes gets one value
+    latched_low: Option<u8>,
+    /// cycles counted towards the next count down
+    prescaler_count: u64,
+    last_cycle: u64,
+}
+impl Timer {
+    pub fn new() -> Timer {
+        Timer::default()
+    }
+    fn count_down(&mut self, mut counts: u64) {
+        while counts > 0 {
+            if self.counter as u64 >= counts {
+                self.counter -= counts as u16;
+                return;
+            }
+            // down to zero, then one more overflows
+            counts -= self.counter as u64 + 1;
+            self.overflow = 1;
+            if self.control & ONE_SHOT != 0 {
+                self.control &= !ENABLE;
+                self.counter = 0;
+                return;
+            }
+            self.counter = self.reload;
+        }
+    }
+}
+impl Device for Timer {
+    fn read(&mut self, offset: u16) -> u8 {
+        match offset {
+            COUNTER_HIGH => {
+                self.latched_low = Some(self.counter as u8);
+                (self.counter >> 8) as u8
+            }
+            COUNTER_LOW => self.latched_low.take().unwrap_or(self.counter as u8),
+            _ => self.peek(offset),
+        }
+    }
+    fn write(&mut self, offset: u16, value: u8) {
+        match offset {
+            COUNTER_HIGH => self.counter = (self.counter & 0x00FF) | ((value as u16) << 8),
+            COUNTER_LOW => self.counter = (self.counter & 0xFF00) | value as u16,
+            RELOAD_HIGH => self.reload = (self.reload & 0x00FF) | ((value as u16) << 8),
+            RELOAD_LOW => self.reload = (self.reload & 0xFF00) | value as u16,
+            PRESCALER => {
+                self.prescaler = value;
+                self.prescaler_count = 0;
+            }
+            CONTROL => self.control = value,
+            // writing anything clears it
+            OVERFLOW => self.overflow = 0,
+            // unused
+            _ => {}
+        }
+    }
+    fn peek(&self, offset: u16) -> u8 {
+        match offset {
+            COUNTER_HIGH => (self.counter >> 8) as u8,
+            COUNTER_LOW => self.latched_low.unwrap_or(self.counter as u8),
+            RELOAD_HIGH => (self.reload >> 8) as u8,
+            RELOAD_LOW => self.reload as u8,
+            PRESCALER => self.prescaler,
+            CONTROL => self.control,
+            OVERFLOW => self.overflow,
+            _ => 0,
+        }
+    }
+    fn tick(&mut self, cycle: u64) {
+        let elapsed = cycle - self.last_cycle;
+        self.last_cycle = cycle;
+        if self.control & ENABLE == 0 {
+            return;
+        }
+        let prescaler = self.prescaler as u64 + 1;
+        let cycles = self.prescaler_count + elapsed;
+        self.prescaler_count = cycles % prescaler;
+        self.count_down(cycles / prescaler);
+    }
+    fn reset(&mut self) {
+        *self = Timer::new();
+    }
+    fn interrupt(&self) -> bool {
+        self.control & INTERRUPT_ENABLE != 0 && self.overflow != 0
+    }
+    fn irq_connected(&self) -> bool {
+        true
+    }
+}
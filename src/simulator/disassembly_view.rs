@@ -0,0 +1,199 @@
+use std::collections::HashSet;
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Paragraph};
+use crate::disassembler;
+use crate::disassembler::symbols::{SymbolTable, SymbolType};
+use crate::simulator::bin_parser;
+use crate::simulator::bin_parser::MAX_INSTRUCTION_LENGTH;
+use crate::simulator::executor::Processor;
+
+/// programs start here, everything below is the zero page and the stack
+const FIRST_ADDRESS: u16 = 0x0200;
+/// rows above the program counter when following it
+const ROWS_ABOVE_PC: u16 = 5;
+
+/// where instructions start in memory, found by decoding from 0200 onwards
+/// it's only worked out again around bytes that get written, instead of every frame
+#[derive(Debug, Default, Clone)]
+pub struct DisassemblyView {
+    /// the length of the instruction at each address, 0 if there isn't one (data or operands)
+    lengths: Vec<u8>,
+    /// addresses the symbol table says have an instruction, so data in between code doesn't misalign what comes after
+    seeds: HashSet<u16>,
+    /// the address at the top of the pane, or None to follow the program counter
+    scroll: Option<u16>,
+    /// rows in the pane the last time it was drawn, so paging moves by a whole pane
+    rows: u16,
+}
+impl DisassemblyView {
+    /// decodes all of memory, for when a new program has been loaded
+    pub fn rebuild(&mut self, memory: &[u8; 65536], symbol_table: &SymbolTable) {
+        self.seeds = symbol_table.lines.keys().copied()
+            .chain(symbol_table.symbols.iter()
+                .filter(|(_, symbol)| symbol.symbol_type == SymbolType::Label)
+                .map(|(address, _)| *address))
+            .collect();
+        self.lengths = vec![0; 0x10000];
+        self.sweep(memory, FIRST_ADDRESS, None);
+    }
+    pub fn record_writes(&mut self, memory: &[u8; 65536], writes: &[(u16, u8)]) {
+        for (address, _) in writes {
+            self.record_write(memory, *address);
+        }
+    }
+    /// decodes again from the instruction the byte is part of, until it lines back up with what was there before
+    pub fn record_write(&mut self, memory: &[u8; 65536], address: u16) {
+        if address < FIRST_ADDRESS || self.lengths.is_empty() {
+            return;
+        }
+        let start = (0..MAX_INSTRUCTION_LENGTH)
+            .map(|offset| address.saturating_sub(offset))
+            .find(|start| *start >= FIRST_ADDRESS && self.lengths[*start as usize] as u16 > address - start)
+            .unwrap_or(address);
+        self.sweep(memory, start, Some(address));
+    }
+    /// decodes forwards from the address, stopping at the first instruction after `changed` that was already there
+    fn sweep(&mut self, memory: &[u8; 65536], start: u16, changed: Option<u16>) {
+        let mut address = start as usize;
+        while address < self.lengths.len() {
+            if let Some(changed) = changed && address > changed as usize && self.lengths[address] != 0 {
+                return;
+            }
+            let length = self.length_at(memory, address as u16);
+            self.lengths[address] = length;
+            let next = (address + length.max(1) as usize).min(self.lengths.len());
+            // operands (or the next byte of data) can't have an instruction
+            self.lengths[address + 1..next].fill(0);
+            address = next;
+        }
+    }
+    fn length_at(&self, memory: &[u8; 65536], address: u16) -> u8 {
+        let Ok((_, operand_bytes)) = bin_parser::parse_instruction(memory, address) else {
+            return 0;
+        };
+        let length = operand_bytes + 1;
+        // an instruction can't run over the start of one the symbol table knows about, so this byte must be data
+        if (1..length as u16).any(|offset| self.seeds.contains(&address.wrapping_add(offset))) {
+            0
+        } else {
+            length
+        }
+    }
+
+    pub fn scroll(&mut self, program_counter: u16, pages: i32) {
+        let top = self.top(program_counter) as i32;
+        self.scroll = Some((top + pages * self.rows.max(1) as i32).clamp(0, 0xFFFF) as u16);
+    }
+    pub fn goto(&mut self, address: u16) {
+        self.scroll = Some(address.saturating_sub(ROWS_ABOVE_PC));
+    }
+    pub fn follow_program_counter(&mut self) {
+        self.scroll = None;
+    }
+
+    fn top(&self, program_counter: u16) -> u16 {
+        self.scroll.unwrap_or(program_counter.saturating_sub(ROWS_ABOVE_PC))
+    }
+
+    pub fn widget(&mut self, cpu: &Processor, symbol_table: &SymbolTable, height: u16) -> Paragraph<'static> {
+        self.rows = height.saturating_sub(2).max(1);
+        let top = self.top(cpu.program_counter);
+        let end = (top as usize + self.rows as usize).min(0x10000);
+
+        // disassembled together so pairs like phpc + jmp can be shown as jsr
+        let starts = (top as usize..end).filter(|address| self.lengths.get(*address).is_some_and(|length| *length != 0)).collect::<Vec<usize>>();
+        let mut instructions = Vec::with_capacity(starts.len());
+        let mut bytes_to_skip = Vec::with_capacity(starts.len());
+        for (index, start) in starts.iter().enumerate() {
+            let (instruction, operand_bytes) = bin_parser::parse_instruction(&cpu.bus.memory, *start as u16)
+                .expect("Instructions in the disassembly should always parse");
+            instructions.push(instruction);
+            // any data before the next instruction gets an empty line, so each line is one address
+            bytes_to_skip.push(starts.get(index + 1).map_or(operand_bytes, |next| (next - start - 1) as u8));
+        }
+        let disassembled_lines = disassembler::disassemble(instructions, bytes_to_skip);
+
+        let lines = (top as usize..end).map(|address| -> Line {
+            let disassembled_line = starts.first()
+                .and_then(|first| address.checked_sub(*first))
+                .and_then(|index| disassembled_lines.get(index))
+                .map_or("", |line| line.as_str());
+            let prefix = if address == cpu.program_counter as usize { "-> " } else { "   " };
+            let mut line = line_for(address as u16, cpu.bus.memory[address], disassembled_line, symbol_table);
+            line.spans.insert(0, prefix.into());
+            line
+        }).collect::<Vec<Line>>();
+
+        let mut block = Block::bordered().title(" Disassembly ");
+        if self.scroll.is_some() {
+            block = block.title_bottom(Line::from(" scrolled - <Home> to follow the PC ".blue()).centered());
+        }
+        Paragraph::new(lines).block(block)
+    }
+}
+
+/// one address in the disassembly, with its byte, the instruction there, and any symbols
+fn line_for(address: u16, value: u8, disassembled_line: &str, symbol_table: &SymbolTable) -> Line<'static> {
+    let mut final_line = format!("0x{address:04x?}: ").yellow() + format!("0x{value:02x?}").green();
+
+    if !disassembled_line.is_empty() {
+        final_line = final_line + " -> ".light_green() + disassembled_line.to_string().light_green();
+    }
+
+    // ------------------------------ SYMBOL TABLE ------------------------------
+    if let Some(symbol) = symbol_table.symbol_uses.get(&address) {
+        let mut symbol = symbol.clone();
+        // remove folder names
+        symbol.name = symbol.name.rsplit_once('/').unwrap_or(("", &symbol.name)).1.to_string();
+        // convert value to hex
+        if let Ok(symbol_value) = symbol.value.parse::<u16>() {
+            symbol.value = format!("{symbol_value:04x}");
+        }
+        // add prefix based on type
+        match symbol.symbol_type {
+            SymbolType::Pointer => {
+                symbol.name = "*".to_string() + &symbol.name;
+                // if you see an into iter on final_line, it's magic trickery so I can keep
+                // the styling while still doing operations on the string
+                let final_line_vec: Vec<Span> = final_line.into_iter().map(|span| {
+                    span.clone().content(span.content.replace(&symbol.value, ""))
+                }).collect();
+                final_line = Line::from(final_line_vec);
+            }
+            SymbolType::Label => {
+                symbol.name = "~".to_string() + &symbol.name;
+                let final_line_vec: Vec<Span> = final_line.into_iter().map(|span| {
+                    span.clone().content(span.content.replace(&("%".to_string() + &symbol.value), ""))
+                }).collect();
+                final_line = Line::from(final_line_vec);
+                symbol.value = "%".to_string() + &symbol.value;
+            }
+            SymbolType::Subroutine => {
+                symbol.name = "~".to_string() + symbol.name.strip_suffix("_SR").expect("Somehow had a subroutine ending in the wrong suffix? Report this to me with your source files.");
+                let final_line_vec: Vec<Span> = final_line.into_iter().map(|span| {
+                    span.clone().content(span.content.replace(&("%".to_string() + &symbol.value), ""))
+                }).collect();
+                final_line = Line::from(final_line_vec);
+                symbol.value = "%".to_string() + &symbol.value;
+            }
+            _ => eprintln!("{symbol:?}")
+        }
+
+        final_line += format!("{}: {}", symbol.name, symbol.value).light_blue()
+    }
+    if let Some(symbol) = symbol_table.symbols.get(&address) {
+        if symbol.name.ends_with("_EndSR") {
+            final_line += format!(" {}", symbol.clone().name.strip_suffix("_EndSR").unwrap()).light_blue()
+        } else if symbol.name.ends_with("_SR") {
+            let mut final_line_vec: Vec<Span> = final_line.into_iter().collect::<Vec<Span>>();
+            final_line_vec.insert(0, format!("sr {}: ", symbol.clone().name.strip_suffix("_SR").unwrap()).blue());
+            final_line = Line::from(final_line_vec);
+        } else {
+            let mut final_line_vec: Vec<Span> = final_line.into_iter().collect::<Vec<Span>>();
+            final_line_vec.insert(0, format!("{}: ", symbol.clone().name).blue());
+            final_line = Line::from(final_line_vec);
+        }
+    }
+    // ------------------------------ END SYMBOL TABLE ------------------------------
+    final_line
+}
@@ -0,0 +1,77 @@
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Paragraph};
+use crate::simulator::devices::lcd;
+use crate::simulator::devices::lcd::Lcd;
+use crate::simulator::devices::seven_segment;
+use crate::simulator::devices::seven_segment::{Brightness, SevenSegment};
+
+/// the lines each segment is drawn on, with the character it's drawn as and its column in the digit
+/// (a is 0 through g at 6, then the decimal point)
+const SEGMENT_LAYOUT: [(usize, usize, char); seven_segment::SEGMENT_COUNT] = [
+    (0, 1, '_'), (1, 2, '|'), (2, 2, '|'), (2, 1, '_'), (2, 0, '|'), (1, 0, '|'), (1, 1, '_'), (2, 3, '.'),
+];
+
+/// the seven segment display with the lcd under it
+pub fn widget(seven_segment: &SevenSegment, lcd: &Lcd) -> Paragraph<'static> {
+    let mut lines: Vec<Line> = Vec::new();
+
+    // ------------------------------ SEVEN SEGMENT ------------------------------
+    let mut rows: [Vec<Span>; 3] = Default::default();
+    // digit 0 is on the right
+    for digit in (0..seven_segment::DIGITS).rev() {
+        // three columns for the digit and one for the decimal point
+        let mut cells: [[Span; 4]; 3] = std::array::from_fn(|_| std::array::from_fn(|_| Span::raw(" ")));
+        for (segment, (row, column, character)) in SEGMENT_LAYOUT.iter().enumerate() {
+            cells[*row][*column] = match seven_segment.brightness(digit, segment) {
+                Brightness::Bright => character.to_string().light_red().bold(),
+                Brightness::Dim => character.to_string().red(),
+                Brightness::Off => Span::raw(" "),
+            };
+        }
+        for (row, cells) in rows.iter_mut().zip(cells) {
+            row.extend(cells);
+            row.push(" ".into());
+        }
+    }
+    lines.extend(rows.map(Line::from));
+    lines.push(Line::default());
+    // ------------------------------ END SEVEN SEGMENT ------------------------------
+
+    // ------------------------------ LCD ------------------------------
+    let cursor = lcd.cursor_position().filter(|_| lcd.display_on);
+    for row in 0..lcd::ROWS {
+        let mut spans = Vec::with_capacity(lcd::COLUMNS);
+        for column in 0..lcd::COLUMNS {
+            let character = if lcd.display_on { lcd_character(lcd.character_at(row, column)) } else { ' ' };
+            let mut span = character.to_string().black().on_green();
+            if cursor == Some((row, column)) {
+                if lcd.blink_on {
+                    span = span.reversed();
+                }
+                if lcd.cursor_on {
+                    span = span.underlined();
+                }
+            }
+            spans.push(span);
+        }
+        lines.push(Line::from(spans));
+    }
+    // ------------------------------ END LCD ------------------------------
+
+    Paragraph::new(lines).block(Block::bordered().title(" Displays "))
+}
+
+/// the character the lcd's font (the HD44780's A00 rom) has for a code
+fn lcd_character(code: u8) -> char {
+    match code {
+        // the custom characters, which can't be drawn in a terminal
+        0x00..=0x0F => '▒',
+        b'\\' => '¥',
+        0x7E => '→',
+        0x7F => '←',
+        0x20..=0x7D => code as char,
+        // half width katakana, in the same order as unicode has them
+        0xA1..=0xDF => char::from_u32(0xFF61 + (code - 0xA1) as u32).unwrap_or(' '),
+        _ => ' ',
+    }
+}
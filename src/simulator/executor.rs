@@ -1,5 +1,26 @@
-use crate::simulator::bin_parser;
-use crate::simulator::bin_parser::{Address, Instruction};
+use crate::simulator::bin_parser::{Address, Instruction, ParseError};
+use crate::simulator::bus::Bus;
+use crate::simulator::decode_cache::DecodeCache;
+use crate::simulator::fault::{Fault, FaultKind, FaultOutcome, OpcodePolicy, StackPolicy};
+use crate::simulator::timing::{CycleTable, INTERRUPT_CYCLES};
+
+/// the status register flag that lets irq's through (nmi's always get through)
+pub const INTERRUPT_ENABLE: u8 = 0b000000_10;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interrupt {
+    Irq,
+    Nmi,
+}
+impl Interrupt {
+    /// where the address of the handler is (high byte first, like the reset vector)
+    pub fn vector(self) -> u16 {
+        match self {
+            Interrupt::Irq => 0xFFFE,
+            Interrupt::Nmi => 0xFFEE,
+        }
+    }
+}
 
 fn calculate_address(address: Address, cpu: &Processor) -> u16 {
     let real_address;
@@ -10,16 +31,31 @@ fn calculate_address(address: Address, cpu: &Processor) -> u16 {
     }
     real_address
 }
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug)]
 pub struct Processor {
     pub accumulator: u8,
     pub registers: [u8; 8],
     pub status_register: u8,
     pub stack_pointer: u8,
-    pub memory: [u8; 65536],
+    pub bus: Bus,
     pub program_counter: u16,
     pub operand1: u8,
     pub operand2: u8,
+    /// number of clock cycles run since the last reset
+    pub cycles: u64,
+    /// number of instructions run since the last reset
+    pub instructions: u64,
+    /// number of clock cycles run since the processor was made, which keeps counting through resets
+    /// (limits on how long to run go by this, so a program that keeps resetting still stops)
+    pub total_cycles: u64,
+    pub timing: CycleTable,
+    pub stack_policy: StackPolicy,
+    pub opcode_policy: OpcodePolicy,
+    /// set by a step that faulted, and cleared by the next one
+    pub fault: Option<Fault>,
+    /// set by a step that started an interrupt after its instruction, and cleared by the next one
+    pub interrupt: Option<Interrupt>,
+    pub decode_cache: DecodeCache,
 }
 impl Default for Processor {
     fn default() -> Self {
@@ -33,10 +69,19 @@ impl Processor {
             registers: [0; 8],
             status_register: 0b010000_00, // accumulator starts at zero, so zero flag is 1
             stack_pointer: 0x00,
-            memory: [0; 65536],
+            bus: Bus::new(),
             program_counter: 0x0000,
             operand1: 0x00,
             operand2: 0x00,
+            cycles: 0,
+            instructions: 0,
+            total_cycles: 0,
+            timing: CycleTable::default(),
+            stack_policy: StackPolicy::default(),
+            opcode_policy: OpcodePolicy::default(),
+            fault: None,
+            interrupt: None,
+            decode_cache: DecodeCache::default(),
         };
         cpu.reset();
         cpu
@@ -46,13 +91,108 @@ impl Processor {
         self.registers = [0; 8];
         self.status_register = 0b010000_00;
         self.stack_pointer = 0x00;
-        let high_byte = self.memory[0xFFFC];
-        let low_byte = self.memory[0xFFFD];
+        self.cycles = 0;
+        self.instructions = 0;
+        self.bus.reset();
+        let high_byte = self.bus.memory[0xFFFC];
+        let low_byte = self.bus.memory[0xFFFD];
         self.program_counter = ((high_byte as u16) << 8) | (low_byte as u16);
     }
+    /// runs one instruction, then lets the devices on the bus catch up to the cycles it took
     pub fn step(&mut self) {
-        let (instruction, instruction_extra_bytes) = bin_parser::parse_instruction(&self.memory, self.program_counter)
-            .expect("The executor should never reach an invalid byte while executing, check your code! (you may have also passed in an invalid file)");
+        // the writes are from the last step or from outside (like the memory view), and either could be to code
+        for (address, _) in self.bus.writes.iter() {
+            self.decode_cache.invalidate(*address);
+        }
+        self.bus.writes.clear();
+        self.fault = None;
+        self.interrupt = None;
+        let opcode = self.bus.memory[self.program_counter as usize];
+        let (instruction, instruction_extra_bytes) = match self.decode_cache.decode(&self.bus.memory, self.program_counter) {
+            Ok(decoded) => decoded,
+            Err(error) => {
+                let kind = match error {
+                    ParseError::InvalidOpcode(opcode) => FaultKind::InvalidOpcode(opcode),
+                    ParseError::InvalidRegister(opcode, register) => FaultKind::InvalidRegister(opcode, register),
+                };
+                match self.opcode_policy {
+                    OpcodePolicy::Reset => self.fault_and_reset(kind, self.timing.cycles(opcode)),
+                    OpcodePolicy::Trap => self.fault = Some(Fault::new(self, kind, FaultOutcome::Halted)),
+                }
+                return;
+            }
+        };
+        if let Some(kind) = Fault::check_stack(self, &instruction) {
+            match self.stack_policy {
+                StackPolicy::Halt => {
+                    self.fault = Some(Fault::new(self, kind, FaultOutcome::Halted));
+                    return;
+                }
+                StackPolicy::Reset => {
+                    self.fault_and_reset(kind, self.timing.cycles(opcode));
+                    return;
+                }
+                StackPolicy::Wrap => self.fault = Some(Fault::new(self, kind, FaultOutcome::Continued)),
+            }
+        }
+        self.execute(instruction, instruction_extra_bytes);
+        self.instructions += 1;
+        let cycles = self.timing.cycles(opcode);
+        self.cycles += cycles;
+        self.total_cycles += cycles;
+        self.bus.tick(self.cycles);
+        self.check_interrupts();
+    }
+    /// starts an interrupt if one is waiting: the program counter (low byte first) and status register are pushed,
+    /// interrupts are disabled, and the handler's address is loaded from the vector
+    fn check_interrupts(&mut self) {
+        let interrupt = if self.bus.nmi_pending {
+            Interrupt::Nmi
+        } else if self.status_register & INTERRUPT_ENABLE != 0 && self.bus.irq() {
+            Interrupt::Irq
+        } else {
+            return;
+        };
+        if let Some(kind) = Fault::check_stack_use(self, 3, 0) {
+            match self.stack_policy {
+                // the interrupt stays waiting
+                StackPolicy::Halt => {
+                    self.fault = Some(Fault::new(self, kind, FaultOutcome::Halted));
+                    return;
+                }
+                StackPolicy::Reset => {
+                    self.fault_and_reset(kind, INTERRUPT_CYCLES);
+                    return;
+                }
+                StackPolicy::Wrap => self.fault = Some(Fault::new(self, kind, FaultOutcome::Continued)),
+            }
+        }
+        match interrupt {
+            Interrupt::Nmi => self.bus.nmi_pending = false,
+            Interrupt::Irq => self.bus.irq_requested = false,
+        }
+        let program_counter = self.program_counter.to_be_bytes();
+        self.push_stack(program_counter[1]);
+        self.push_stack(program_counter[0]);
+        self.push_stack(self.status_register);
+        self.status_register &= !INTERRUPT_ENABLE;
+        let vector = interrupt.vector();
+        let high_byte = self.bus.memory[vector as usize];
+        let low_byte = self.bus.memory[vector as usize + 1];
+        self.program_counter = ((high_byte as u16) << 8) | (low_byte as u16);
+        self.interrupt = Some(interrupt);
+        self.cycles += INTERRUPT_CYCLES;
+        self.total_cycles += INTERRUPT_CYCLES;
+        self.bus.tick(self.cycles);
+    }
+    /// the cycles are how long it took to get to the fault, they only go towards the total since the reset clears the rest
+    fn fault_and_reset(&mut self, kind: FaultKind, cycles: u64) {
+        let fault = Fault::new(self, kind, FaultOutcome::Reset);
+        self.total_cycles += cycles;
+        self.reset();
+        self.fault = Some(fault);
+    }
+    fn execute(&mut self, instruction: Instruction, instruction_extra_bytes: u8) {
         match instruction {
             Instruction::Add(one_register, two_register) => {
                 if let Some(register) = one_register {
@@ -65,7 +205,7 @@ impl Processor {
                     if carry > 0 {
                         self.status_register |= 0b100000_00
                     } else {
-                        self.status_register &= 0b011111_00
+                        self.status_register &= 0b011111_11
                     }
                     self.update_status_two_operands(self.accumulator, register_value as u8);
                 } else if let Some((register1, register2)) = two_register {
@@ -78,7 +218,7 @@ impl Processor {
                     if carry > 0 {
                         self.status_register |= 0b100000_00
                     } else {
-                        self.status_register &= 0b011111_00
+                        self.status_register &= 0b011111_11
                     }
                     self.update_status_two_operands(register1_value as u8, register2_value as u8);
                 }
@@ -94,7 +234,7 @@ impl Processor {
                     if carry_out {
                         self.status_register |= 0b100000_00
                     } else {
-                        self.status_register &= 0b011111_00
+                        self.status_register &= 0b011111_11
                     }
 
                     self.update_status_two_operands(self.accumulator, self.registers[register as usize]);
@@ -108,7 +248,7 @@ impl Processor {
                     if carry_out {
                         self.status_register |= 0b100000_00
                     } else {
-                        self.status_register &= 0b011111_00
+                        self.status_register &= 0b011111_11
                     }
                     
                     self.update_status_two_operands((register1_value & 0b0000_0000_1111_1111) as u8, register2_value);
@@ -194,7 +334,7 @@ impl Processor {
                 if carry_out > 0 {
                     self.status_register |= 0b100000_00;
                 } else {
-                    self.status_register &= 0b011111_00;
+                    self.status_register &= 0b011111_11;
                 }
             }
             Instruction::ShiftLeft => {
@@ -212,7 +352,7 @@ impl Processor {
                 if carry_out > 0 {
                     self.status_register |= 0b100000_00;
                 } else {
-                    self.status_register &= 0b011111_00;
+                    self.status_register &= 0b011111_11;
                 }
             }
             Instruction::Noop => {}
@@ -220,7 +360,7 @@ impl Processor {
                 self.status_register |= 0b100000_00
             }
             Instruction::ClearCarry => {
-                self.status_register &= 0b011111_00
+                self.status_register &= 0b011111_11
             }
             Instruction::PushRegisterToStack(register) => {
                 self.push_stack(self.registers[register as usize]);
@@ -230,14 +370,14 @@ impl Processor {
             }
             Instruction::LoadAccumulator(address, immediate) => {
                 if let Some(address) = address {
-                    self.accumulator = self.memory[calculate_address(address, self) as usize];
+                    self.accumulator = self.bus.read(calculate_address(address, self));
                 } else if let Some(immediate) = immediate {
                     self.accumulator = immediate;
                 }
                 self.update_status_one_operand(self.accumulator);
             }
             Instruction::StoreAccumulator(address) => {
-                self.memory[calculate_address(address, self) as usize] = self.accumulator;
+                self.bus.write(calculate_address(address, self), self.accumulator);
             }
             Instruction::CopyAccumulatorToRegister(register) => {
                 self.registers[register as usize] = self.accumulator;
@@ -334,47 +474,61 @@ impl Processor {
             Instruction::PopProgramCounterSubroutine => {
                 let program_counter_big = self.pop_stack();
                 let program_counter_small = self.pop_stack();
-                self.program_counter = ((program_counter_big as u16) << 8 | (program_counter_small as u16)) + 3;
+                self.program_counter = ((program_counter_big as u16) << 8 | (program_counter_small as u16)).wrapping_add(3);
+            }
+            Instruction::SetInterruptEnable => {
+                self.status_register |= INTERRUPT_ENABLE;
+            }
+            Instruction::ClearInterruptEnable => {
+                self.status_register &= !INTERRUPT_ENABLE;
+            }
+            Instruction::ReturnFromInterrupt => {
+                self.status_register = self.pop_stack();
+                let program_counter_big = self.pop_stack();
+                let program_counter_small = self.pop_stack();
+                // the interrupt pushed the address of the next instruction, so it's not skipped over
+                self.program_counter = (program_counter_big as u16) << 8 | (program_counter_small as u16);
+                return;
             }
         }
         self.program_counter = self.program_counter.wrapping_add(1 + instruction_extra_bytes as u16);
     }
-    fn push_stack(&mut self, value: u8) {
-        self.memory[(self.stack_pointer as u16 + 0x0100) as usize] = value;
-        // not using wrapping add/sub so we can catch stack overflows
-        self.stack_pointer += 1;
+    pub fn push_stack(&mut self, value: u8) {
+        self.bus.write(self.stack_pointer as u16 + 0x0100, value);
+        // overflows are caught before the instruction runs, so this only wraps if the stack policy allows it
+        self.stack_pointer = self.stack_pointer.wrapping_add(1);
     }
     fn pop_stack(&mut self) -> u8 {
-        self.stack_pointer -= 1;
+        self.stack_pointer = self.stack_pointer.wrapping_sub(1);
         
-        self.memory[(self.stack_pointer as u16 + 0x0100) as usize]
+        self.bus.read(self.stack_pointer as u16 + 0x0100)
     }
     /// note: cannot update the carry, that must be done manually
     fn update_status_two_operands(&mut self, operand1: u8, operand2: u8) {
         if operand1 > operand2 {
             self.status_register |= 0b001000_00;
         } else {
-            self.status_register &= 0b110111_00;
+            self.status_register &= 0b110111_11;
         }
         if operand1 < operand2 {
             self.status_register |= 0b000100_00;
         } else {
-            self.status_register &= 0b111011_00;
+            self.status_register &= 0b111011_11;
         }
         if operand1 == operand2 {
             self.status_register |= 0b000010_00;
         } else {
-            self.status_register &= 0b111101_00;
+            self.status_register &= 0b111101_11;
         }
         if self.accumulator == 0 {
             self.status_register |= 0b010000_00;
         } else {
-            self.status_register &= 0b101111_00;
+            self.status_register &= 0b101111_11;
         }
         if self.accumulator & 0b1000_0000 > 0 {
             self.status_register |= 0b000001_00;
         } else {
-            self.status_register &= 0b111110_00;
+            self.status_register &= 0b111110_11;
         }
         self.operand1 = operand1;
         self.operand2 = operand2;
@@ -384,27 +538,27 @@ impl Processor {
         if self.accumulator > other_operand {
             self.status_register |= 0b001000_00;
         } else {
-            self.status_register &= 0b110111_00;
+            self.status_register &= 0b110111_11;
         }
         if self.accumulator < other_operand {
             self.status_register |= 0b000100_00;
         } else {
-            self.status_register &= 0b111011_00;
+            self.status_register &= 0b111011_11;
         }
         if self.accumulator == other_operand {
             self.status_register |= 0b000010_00;
         } else {
-            self.status_register &= 0b111101_00;
+            self.status_register &= 0b111101_11;
         }
         if self.accumulator == 0 {
             self.status_register |= 0b010000_00;
         } else {
-            self.status_register &= 0b101111_00;
+            self.status_register &= 0b101111_11;
         }
         if self.accumulator & 0b1000_0000 > 0 {
             self.status_register |= 0b000001_00;
         } else {
-            self.status_register &= 0b111110_00;
+            self.status_register &= 0b111110_11;
         }
         self.operand1 = other_operand;
         self.operand2 = 0x00;
@@ -412,16 +566,16 @@ impl Processor {
     /// note: cannot update the carry, that must be done manually
     fn update_status_no_operands(&mut self) {
         // not greater than, not less than, not equal to (since there's no other operand)
-        self.status_register &= 0b110001_00;
+        self.status_register &= 0b110001_11;
         if self.accumulator == 0 {
             self.status_register |= 0b010000_00;
         } else {
-            self.status_register &= 0b101111_00;
+            self.status_register &= 0b101111_11;
         }
         if self.accumulator & 0b1000_0000 > 0 {
             self.status_register |= 0b000001_00;
         } else {
-            self.status_register &= 0b111110_00;
+            self.status_register &= 0b111110_11;
         }
         self.operand1 = 0x00;
         self.operand2 = 0x00;
@@ -0,0 +1,178 @@
+use crate::disassembler::symbols::SymbolTable;
+use crate::simulator::bin_parser::Instruction;
+use crate::simulator::call_stack;
+use crate::simulator::call_stack::CallFrame;
+use crate::simulator::executor::Processor;
+use crate::simulator::trace::Labels;
+
+/// what the cpu does when the stack overflows or underflows
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum StackPolicy {
+    /// stop before the instruction runs, so the program can be looked at
+    #[default]
+    Halt,
+    /// let the stack pointer wrap around, like the hardware does
+    Wrap,
+    /// reset the cpu
+    Reset,
+}
+impl StackPolicy {
+    pub fn parse(text: &str) -> Option<StackPolicy> {
+        match text {
+            "halt" => Some(StackPolicy::Halt),
+            "wrap" => Some(StackPolicy::Wrap),
+            "reset" => Some(StackPolicy::Reset),
+            _ => None,
+        }
+    }
+}
+
+/// what the cpu does when it reaches an opcode (or a register) that doesn't exist
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum OpcodePolicy {
+    /// reset the cpu, which is what the ISA says should happen
+    #[default]
+    Reset,
+    /// stop before the opcode, so the program can be looked at
+    Trap,
+}
+impl OpcodePolicy {
+    pub fn parse(text: &str) -> Option<OpcodePolicy> {
+        match text {
+            "reset" => Some(OpcodePolicy::Reset),
+            "trap" => Some(OpcodePolicy::Trap),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FaultKind {
+    /// pushing would take the stack pointer past ff
+    StackOverflow,
+    /// popping would take the stack pointer below 00
+    StackUnderflow,
+    /// the opcode isn't in the ISA (ff never is)
+    InvalidOpcode(u8),
+    /// the instruction names a register past r7 (the opcode, then the register)
+    InvalidRegister(u8, u8),
+}
+
+/// what the cpu did about a fault, decided by the policy for its kind
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FaultOutcome {
+    /// the instruction didn't run, so stepping again faults again
+    Halted,
+    /// the instruction ran anyway
+    Continued,
+    /// the cpu was reset
+    Reset,
+}
+
+/// something the program did that the hardware can't do sensibly, recorded by the step that caused it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fault {
+    pub kind: FaultKind,
+    pub outcome: FaultOutcome,
+    /// address of the instruction that caused it
+    pub program_counter: u16,
+    pub stack_pointer: u8,
+    /// the subroutine calls on the stack when it happened, innermost first
+    pub calls: Vec<CallFrame>,
+}
+impl Fault {
+    /// records a fault at the current instruction, before the cpu does anything about it
+    pub fn new(cpu: &Processor, kind: FaultKind, outcome: FaultOutcome) -> Fault {
+        Fault {
+            kind,
+            outcome,
+            program_counter: cpu.program_counter,
+            stack_pointer: cpu.stack_pointer,
+            calls: call_stack::reconstruct(cpu),
+        }
+    }
+
+    /// the stack fault the instruction would cause if it ran now
+    pub fn check_stack(cpu: &Processor, instruction: &Instruction) -> Option<FaultKind> {
+        let (pushes, pops) = match instruction {
+            Instruction::PushRegisterToStack(_) => (1, 0),
+            Instruction::PushProgramCounter => (2, 0),
+            Instruction::PopRegisterFromStack(_) => (0, 1),
+            Instruction::PopProgramCounter | Instruction::PopProgramCounterSubroutine => (0, 2),
+            Instruction::ReturnFromInterrupt => (0, 3),
+            _ => return None,
+        };
+        Fault::check_stack_use(cpu, pushes, pops)
+    }
+
+    /// the stack fault pushing and then popping the given number of bytes would cause
+    pub fn check_stack_use(cpu: &Processor, pushes: u16, pops: u16) -> Option<FaultKind> {
+        // the stack pointer points at the next free byte, so it can't point past ff
+        if cpu.stack_pointer as u16 + pushes > 0xFF {
+            Some(FaultKind::StackOverflow)
+        } else if (cpu.stack_pointer as u16) < pops {
+            Some(FaultKind::StackUnderflow)
+        } else {
+            None
+        }
+    }
+
+    pub fn halted(&self) -> bool {
+        self.outcome == FaultOutcome::Halted
+    }
+
+    /// one line, like `stack overflow at 0234 (sp = ff)` or `invalid opcode ff at 0300 (sp = 02, reset the cpu)`
+    pub fn describe(&self) -> String {
+        let kind = match self.kind {
+            FaultKind::StackOverflow => "stack overflow".to_string(),
+            FaultKind::StackUnderflow => "stack underflow".to_string(),
+            FaultKind::InvalidOpcode(opcode) => format!("invalid opcode {opcode:02x}"),
+            FaultKind::InvalidRegister(opcode, register) => format!("invalid register {register:02x} for opcode {opcode:02x}"),
+        };
+        let outcome = match self.outcome {
+            FaultOutcome::Halted => "",
+            FaultOutcome::Continued => ", continued",
+            FaultOutcome::Reset => ", reset the cpu",
+        };
+        format!("{kind} at {:04x} (sp = {:02x}{outcome})", self.program_counter, self.stack_pointer)
+    }
+
+    /// the calls that led to the fault, innermost first, like `SEND_BYTE called from 0210 (serialtest.LOOP+3)`
+    /// the same call repeated (recursion, usually the cause of an overflow) is only listed once
+    pub fn call_chain(&self, symbol_table: &SymbolTable) -> Vec<String> {
+        let labels = Labels::new(symbol_table);
+        let mut chain: Vec<(String, usize)> = Vec::new();
+        for call in self.calls.iter() {
+            let line = format!("{} called from {}",
+                               call_stack::subroutine_name(symbol_table, call.target),
+                               location(&labels, call.call_address));
+            match chain.last_mut() {
+                Some((last, count)) if *last == line => *count += 1,
+                _ => chain.push((line, 1)),
+            }
+        }
+        chain.into_iter()
+            .map(|(line, count)| if count == 1 { line } else { format!("{line} ({count} times)") })
+            .collect()
+    }
+
+    /// the description and call chain, for printing
+    pub fn report(&self, symbol_table: &SymbolTable) -> String {
+        let labels = Labels::new(symbol_table);
+        let name = labels.name_at(self.program_counter);
+        let mut report = if name.is_empty() { self.describe() } else { format!("{} in {name}", self.describe()) };
+        for call in self.call_chain(symbol_table) {
+            report += &format!("\n  {call}");
+        }
+        if self.calls.is_empty() {
+            report += "\n  (no calls on the stack)";
+        }
+        report
+    }
+}
+
+/// an address and the label it's at, if there is one
+fn location(labels: &Labels, address: u16) -> String {
+    let name = labels.name_at(address);
+    if name.is_empty() { format!("{address:04x}") } else { format!("{address:04x} ({name})") }
+}
@@ -0,0 +1,45 @@
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Paragraph};
+use crate::simulator::devices::framebuffer;
+use crate::simulator::devices::framebuffer::Framebuffer;
+
+/// code page 437, which is the font the vga text controller will use
+const CODE_PAGE_437: [char; 256] = [
+    ' ', '☺', '☻', '♥', '♦', '♣', '♠', '•', '◘', '○', '◙', '♂', '♀', '♪', '♫', '☼',
+    '►', '◄', '↕', '‼', '¶', '§', '▬', '↨', '↑', '↓', '→', '←', '∟', '↔', '▲', '▼',
+    ' ', '!', '"', '#', '$', '%', '&', '\'', '(', ')', '*', '+', ',', '-', '.', '/',
+    '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', ':', ';', '<', '=', '>', '?',
+    '@', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M', 'N', 'O',
+    'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', '[', '\\', ']', '^', '_',
+    '`', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o',
+    'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', '{', '|', '}', '~', '⌂',
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', ' ',
+];
+
+/// the 16 text mode colors, in the order of their numbers in an attribute
+const COLORS: [Color; 16] = [
+    Color::Black, Color::Blue, Color::Green, Color::Cyan, Color::Red, Color::Magenta, Color::Yellow, Color::Gray,
+    Color::DarkGray, Color::LightBlue, Color::LightGreen, Color::LightCyan, Color::LightRed, Color::LightMagenta,
+    Color::LightYellow, Color::White,
+];
+
+/// the whole screen, which needs 42x27 with the border
+pub fn widget(framebuffer: &Framebuffer) -> Paragraph<'static> {
+    let lines: Vec<Line> = (0..framebuffer::ROWS).map(|row| {
+        Line::from((0..framebuffer::COLUMNS).map(|column| {
+            let (character, attribute) = framebuffer.cell(row, column);
+            let style = Style::new()
+                .fg(COLORS[(attribute & 0x0F) as usize])
+                .bg(COLORS[(attribute >> 4) as usize]);
+            Span::styled(CODE_PAGE_437[character as usize].to_string(), style)
+        }).collect::<Vec<Span>>())
+    }).collect();
+    Paragraph::new(lines).block(Block::bordered().title(" Framebuffer "))
+}
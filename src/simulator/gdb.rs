@@ -0,0 +1,481 @@
+use std::collections::HashSet;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use crate::simulator::{load_processor, Options};
+use crate::simulator::devices::serial::SerialPort;
+use crate::simulator::executor::Processor;
+use crate::simulator::headless::{END_LOOP_END, END_LOOP_START};
+use crate::disassembler::symbols::SymbolTable;
+
+/// how many instructions to run between checking if gdb wants to interrupt a continue
+const INTERRUPT_CHECK_INTERVAL: u64 = 4096;
+const PACKET_SIZE: usize = 0x4000;
+
+/// a, r0-r7, sr, sp, pc (in the order of the target description)
+const REGISTER_COUNT: usize = 12;
+const PC_REGISTER: usize = 11;
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.goldasm.gold.core">
+    <flags id="gold_status" size="1">
+      <field name="i" start="1" end="1"/>
+      <field name="n" start="2" end="2"/>
+      <field name="e" start="3" end="3"/>
+      <field name="l" start="4" end="4"/>
+      <field name="g" start="5" end="5"/>
+      <field name="z" start="6" end="6"/>
+      <field name="c" start="7" end="7"/>
+    </flags>
+    <reg name="a" bitsize="8" type="uint8" regnum="0"/>
+    <reg name="r0" bitsize="8" type="uint8"/>
+    <reg name="r1" bitsize="8" type="uint8"/>
+    <reg name="r2" bitsize="8" type="uint8"/>
+    <reg name="r3" bitsize="8" type="uint8"/>
+    <reg name="r4" bitsize="8" type="uint8"/>
+    <reg name="r5" bitsize="8" type="uint8"/>
+    <reg name="r6" bitsize="8" type="uint8"/>
+    <reg name="r7" bitsize="8" type="uint8"/>
+    <reg name="sr" bitsize="8" type="gold_status"/>
+    <reg name="sp" bitsize="8" type="uint8"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+  </feature>
+</target>
+"#;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum StopReason {
+    /// a step finished, a breakpoint was hit, or gdb interrupted us
+    Trap,
+    /// the program jumped to the infinite loop at the end of memory
+    EndLoop,
+    /// the cpu faulted and halted
+    Fault,
+}
+
+/// a gdb remote serial protocol server for one client
+#[derive(Debug)]
+struct GdbServer {
+    cpu: Processor,
+    binary_path: String,
+    /// for naming the subroutine a fault happened in
+    symbol_table: SymbolTable,
+    device_options: crate::simulator::devices::DeviceOptions,
+    client: TcpStream,
+    /// bytes read from the client that haven't been made into a packet yet
+    received: Vec<u8>,
+    no_ack: bool,
+    /// set once gdb has detached or killed the program
+    finished: bool,
+    software_breakpoints: HashSet<u16>,
+    hardware_breakpoints: HashSet<u16>,
+}
+
+/// waits for gdb to connect on localhost, then debugs the binary until it detaches
+pub fn serve(binary_path: &str, symbol_table: SymbolTable, options: Options, port: u16) {
+    let device_options = options.open_devices();
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .unwrap_or_else(|error| panic!("Could not listen on port {port} for gdb ({error})!"));
+    eprintln!("INFO: Waiting for gdb on 127.0.0.1:{port} (target remote :{port})");
+    let (client, address) = listener.accept().expect("Failed to accept the gdb connection!");
+    let _ = client.set_nodelay(true);
+    eprintln!("INFO: gdb connected from {address}");
+
+    let mut server = GdbServer {
+        cpu: load_processor(binary_path, &device_options),
+        binary_path: binary_path.to_string(),
+        symbol_table,
+        device_options,
+        client,
+        received: Vec::new(),
+        no_ack: false,
+        finished: false,
+        software_breakpoints: HashSet::new(),
+        hardware_breakpoints: HashSet::new(),
+    };
+    server.run();
+    eprintln!("INFO: gdb disconnected");
+}
+
+impl GdbServer {
+    fn run(&mut self) {
+        while !self.finished
+            && let Some(packet) = self.read_packet()
+        {
+            if let Some(reply) = self.handle(&packet) {
+                self.send_packet(&reply);
+            }
+        }
+    }
+
+    /// returns the reply to send, if the packet has one
+    fn handle(&mut self, packet: &str) -> Option<String> {
+        let (command, arguments) = packet.split_at(packet.chars().next().map_or(0, char::len_utf8));
+        let reply = match command {
+            // always stopped with SIGTRAP
+            "?" => "S05".to_string(),
+            "g" => (0..REGISTER_COUNT).map(|register| self.read_register(register)).collect(),
+            "G" => {
+                let mut remaining = arguments;
+                for register in 0..REGISTER_COUNT {
+                    let width = if register == PC_REGISTER { 4 } else { 2 };
+                    if remaining.len() < width {
+                        break;
+                    }
+                    let (value, rest) = remaining.split_at(width);
+                    if !self.write_register(register, value) {
+                        return Some("E01".to_string());
+                    }
+                    remaining = rest;
+                }
+                "OK".to_string()
+            }
+            "p" => match usize::from_str_radix(arguments, 16) {
+                Ok(register) if register < REGISTER_COUNT => self.read_register(register),
+                _ => "E01".to_string(),
+            },
+            "P" => {
+                let written = arguments.split_once('=').is_some_and(|(register, value)| {
+                    usize::from_str_radix(register, 16).is_ok_and(|register| self.write_register(register, value))
+                });
+                if written { "OK".to_string() } else { "E01".to_string() }
+            }
+            "m" => match parse_address_length(arguments) {
+                Some((address, length)) => (0..length)
+                    // peek so gdb looking at a device doesn't change it
+                    .map(|offset| format!("{:02x}", self.cpu.bus.peek(address + offset)))
+                    .collect(),
+                None => "E01".to_string(),
+            },
+            "M" => {
+                let written = arguments.split_once(':').and_then(|(range, data)| {
+                    let (address, length) = parse_address_length(range)?;
+                    let bytes = decode_hex(data)?;
+                    if bytes.len() != length as usize {
+                        return None;
+                    }
+                    for (offset, byte) in bytes.iter().enumerate() {
+                        self.cpu.bus.write(address + offset as u16, *byte);
+                    }
+                    Some(())
+                });
+                if written.is_some() { "OK".to_string() } else { "E01".to_string() }
+            }
+            "s" => {
+                if self.resume_at(arguments).is_none() {
+                    return Some("E01".to_string());
+                }
+                self.cpu.step();
+                self.print_serial_output();
+                let stop_reason = if self.check_fault() { StopReason::Fault } else { StopReason::Trap };
+                self.stop_reply(stop_reason)
+            }
+            "c" => {
+                if self.resume_at(arguments).is_none() {
+                    return Some("E01".to_string());
+                }
+                let stop_reason = self.continue_execution();
+                if stop_reason == StopReason::EndLoop {
+                    self.send_console("Program finished (reached the end loop)\n");
+                }
+                self.stop_reply(stop_reason)
+            }
+            "Z" | "z" => {
+                let mut parts = arguments.split(',');
+                let kind = parts.next();
+                let address = parts.next().and_then(|address| u16::from_str_radix(address, 16).ok());
+                let breakpoints = match kind {
+                    Some("0") => Some(&mut self.software_breakpoints),
+                    Some("1") => Some(&mut self.hardware_breakpoints),
+                    _ => None,
+                };
+                match (breakpoints, address) {
+                    (Some(breakpoints), Some(address)) => {
+                        if command == "Z" {
+                            breakpoints.insert(address);
+                        } else {
+                            breakpoints.remove(&address);
+                        }
+                        "OK".to_string()
+                    }
+                    // watchpoints aren't supported
+                    (None, _) => String::new(),
+                    (_, None) => "E01".to_string(),
+                }
+            }
+            "H" => "OK".to_string(),
+            "T" => "OK".to_string(),
+            "R" => {
+                // restart, gdb doesn't expect a reply
+                self.cpu = load_processor(&self.binary_path, &self.device_options);
+                return None;
+            }
+            "D" => {
+                self.finished = true;
+                "OK".to_string()
+            }
+            "k" => {
+                self.finished = true;
+                return None;
+            }
+            "q" | "Q" => return self.handle_query(packet),
+            "v" if packet.starts_with("vKill") => {
+                self.finished = true;
+                "OK".to_string()
+            }
+            // anything we don't know gets an empty reply, which tells gdb it isn't supported
+            _ => String::new(),
+        };
+        Some(reply)
+    }
+
+    fn handle_query(&mut self, packet: &str) -> Option<String> {
+        let name = packet.split([':', ',']).next().unwrap_or(packet);
+        let reply = match name {
+            "qSupported" => format!("PacketSize={PACKET_SIZE:x};qXfer:features:read+;QStartNoAckMode+"),
+            "QStartNoAckMode" => {
+                // the ok still gets acknowledged, so no_ack is set once it's been sent
+                self.send_packet("OK");
+                self.no_ack = true;
+                return None;
+            }
+            "qXfer" => {
+                let Some(request) = packet.strip_prefix("qXfer:features:read:target.xml:") else {
+                    return Some("E00".to_string());
+                };
+                let Some((offset, length)) = request.split_once(',')
+                    .and_then(|(offset, length)| Some((usize::from_str_radix(offset, 16).ok()?, usize::from_str_radix(length, 16).ok()?)))
+                else {
+                    return Some("E01".to_string());
+                };
+                let start = offset.min(TARGET_XML.len());
+                let end = (offset + length).min(TARGET_XML.len());
+                let marker = if end == TARGET_XML.len() { 'l' } else { 'm' };
+                format!("{marker}{}", &TARGET_XML[start..end])
+            }
+            "qAttached" => "1".to_string(),
+            "qC" => "QC1".to_string(),
+            "qfThreadInfo" => "m1".to_string(),
+            "qsThreadInfo" => "l".to_string(),
+            "qOffsets" => "Text=0;Data=0;Bss=0".to_string(),
+            "qRcmd" => self.handle_monitor(packet),
+            _ => String::new(),
+        };
+        Some(reply)
+    }
+
+    /// `monitor` commands, the command is sent as hex
+    fn handle_monitor(&mut self, packet: &str) -> String {
+        let command = packet.split_once(',')
+            .and_then(|(_, command)| decode_hex(command))
+            .map(|command| String::from_utf8_lossy(&command).trim().to_string())
+            .unwrap_or_default();
+        let output = match command.as_str() {
+            "reset" => {
+                self.cpu = load_processor(&self.binary_path, &self.device_options);
+                format!("Reset, pc = {:04x}\n", self.cpu.program_counter)
+            }
+            "cycles" => format!("{} cycles ({} instructions) since reset\n", self.cpu.cycles, self.cpu.instructions),
+            _ => "Monitor commands: reset, cycles\n".to_string(),
+        };
+        self.send_console(&output);
+        "OK".to_string()
+    }
+
+    /// a fault is a SIGSEGV, and everything else is a SIGTRAP
+    fn stop_reply(&self, stop_reason: StopReason) -> String {
+        if stop_reason == StopReason::Fault { "S0b".to_string() } else { "S05".to_string() }
+    }
+
+    /// reports a fault from the last step on the console, returning whether the cpu halted
+    fn check_fault(&mut self) -> bool {
+        let Some(fault) = self.cpu.fault.clone() else {
+            return false;
+        };
+        self.send_console(&format!("{}\n", fault.report(&self.symbol_table)));
+        fault.halted()
+    }
+
+    /// `s` and `c` can have an address to resume from
+    fn resume_at(&mut self, address: &str) -> Option<()> {
+        if !address.is_empty() {
+            self.cpu.program_counter = u16::from_str_radix(address, 16).ok()?;
+        }
+        Some(())
+    }
+
+    fn continue_execution(&mut self) -> StopReason {
+        let mut instructions = 0u64;
+        loop {
+            self.cpu.step();
+            self.print_serial_output();
+            if self.check_fault() {
+                return StopReason::Fault;
+            }
+            let program_counter = self.cpu.program_counter;
+            if self.software_breakpoints.contains(&program_counter) || self.hardware_breakpoints.contains(&program_counter) {
+                return StopReason::Trap;
+            }
+            if (END_LOOP_START..=END_LOOP_END).contains(&program_counter) {
+                return StopReason::EndLoop;
+            }
+            instructions += 1;
+            if instructions.is_multiple_of(INTERRUPT_CHECK_INTERVAL) && self.interrupt_requested() {
+                return StopReason::Trap;
+            }
+        }
+    }
+
+    /// checks for the ctrl-c byte gdb sends to interrupt a running target
+    fn interrupt_requested(&mut self) -> bool {
+        self.client.set_nonblocking(true).expect("Failed to poll the gdb connection!");
+        let mut buffer = [0; 256];
+        let read = self.client.read(&mut buffer);
+        self.client.set_nonblocking(false).expect("Failed to poll the gdb connection!");
+        match read {
+            Ok(bytes_read) => {
+                let interrupted = buffer[..bytes_read].contains(&0x03);
+                self.received.extend(buffer[..bytes_read].iter().filter(|byte| **byte != 0x03));
+                // a closed connection also stops the program
+                interrupted || bytes_read == 0
+            }
+            Err(error) => error.kind() != ErrorKind::WouldBlock,
+        }
+    }
+
+    fn read_register(&self, register: usize) -> String {
+        match register {
+            0 => format!("{:02x}", self.cpu.accumulator),
+            1..=8 => format!("{:02x}", self.cpu.registers[register - 1]),
+            9 => format!("{:02x}", self.cpu.status_register),
+            10 => format!("{:02x}", self.cpu.stack_pointer),
+            // big endian like the rest of the isa
+            PC_REGISTER => format!("{:04x}", self.cpu.program_counter),
+            _ => String::new(),
+        }
+    }
+    fn write_register(&mut self, register: usize, value: &str) -> bool {
+        if register == PC_REGISTER {
+            let Ok(value) = u16::from_str_radix(value, 16) else {
+                return false;
+            };
+            self.cpu.program_counter = value;
+            return true;
+        }
+        let Ok(value) = u8::from_str_radix(value, 16) else {
+            return false;
+        };
+        match register {
+            0 => self.cpu.accumulator = value,
+            1..=8 => self.cpu.registers[register - 1] = value,
+            9 => self.cpu.status_register = value,
+            10 => self.cpu.stack_pointer = value,
+            _ => return false,
+        }
+        true
+    }
+
+    fn print_serial_output(&mut self) {
+        let serial = self.cpu.bus.device_mut::<SerialPort>().expect("The serial port should always be on the bus");
+        let output = serial.take_output();
+        if !output.is_empty() {
+            let mut stdout = std::io::stdout();
+            stdout.write_all(&output).expect("Failed to write serial output to stdout!");
+            stdout.flush().expect("Failed to write serial output to stdout!");
+        }
+    }
+
+    // ------------------------------ PACKETS ------------------------------
+    /// reads the next `$packet#checksum`, returns None if the connection closed
+    fn read_packet(&mut self) -> Option<String> {
+        loop {
+            // drop anything before the start of a packet (acks and stray interrupts)
+            if let Some(start) = self.received.iter().position(|byte| *byte == b'$') {
+                self.received.drain(..start);
+                if let Some(end) = self.received.iter().position(|byte| *byte == b'#')
+                    && self.received.len() >= end + 3
+                {
+                    let data = self.received[1..end].to_vec();
+                    let checksum = std::str::from_utf8(&self.received[end + 1..end + 3]).ok()
+                        .and_then(|checksum| u8::from_str_radix(checksum, 16).ok());
+                    self.received.drain(..end + 3);
+                    if !self.no_ack {
+                        if checksum != Some(checksum_of(&data)) {
+                            self.write_raw(b"-")?;
+                            continue;
+                        }
+                        self.write_raw(b"+")?;
+                    }
+                    return Some(String::from_utf8_lossy(&unescape(&data)).to_string());
+                }
+            } else {
+                self.received.clear();
+            }
+            let mut buffer = [0; 1024];
+            match self.client.read(&mut buffer) {
+                Ok(0) => return None,
+                Ok(bytes_read) => self.received.extend_from_slice(&buffer[..bytes_read]),
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(_) => return None,
+            }
+        }
+    }
+    fn send_packet(&mut self, data: &str) {
+        let packet = format!("${data}#{:02x}", checksum_of(data.as_bytes()));
+        // gdb nacks bad packets, which shouldn't happen over tcp, so the ack isn't waited for
+        let _ = self.write_raw(packet.as_bytes());
+    }
+    /// text for gdb to print, sent before the reply to the command that caused it
+    fn send_console(&mut self, text: &str) {
+        self.send_packet(&format!("O{}", encode_hex(text.as_bytes())));
+    }
+    fn write_raw(&mut self, bytes: &[u8]) -> Option<()> {
+        self.client.write_all(bytes).ok()
+    }
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
+
+/// undoes the `}` escaping used in binary data
+fn unescape(data: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(data.len());
+    let mut bytes = data.iter();
+    while let Some(byte) = bytes.next() {
+        if *byte == b'}' {
+            if let Some(escaped) = bytes.next() {
+                result.push(escaped ^ 0x20);
+            }
+        } else {
+            result.push(*byte);
+        }
+    }
+    result
+}
+
+/// parses `address,length` (both hex)
+fn parse_address_length(text: &str) -> Option<(u16, u16)> {
+    let (address, length) = text.split_once(',')?;
+    let address = u32::from_str_radix(address, 16).ok()?;
+    let length = u32::from_str_radix(length, 16).ok()?;
+    // gdb may ask for addresses past the 16 bit space, which don't exist (and don't wrap around to 0000)
+    if address.checked_add(length).is_none_or(|end| end > 0x10000) {
+        return None;
+    }
+    Some((address as u16, length.min(PACKET_SIZE as u32 / 2) as u16))
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len()).step_by(2)
+        .map(|index| u8::from_str_radix(text.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
@@ -0,0 +1,90 @@
+use crossterm::event::KeyCode;
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Paragraph};
+use crate::simulator::devices::gpio;
+use crate::simulator::devices::gpio::Gpio;
+
+const SWITCHES_PER_BANK: usize = 8;
+
+/// shows the leds, switches and buttons, and lets them be changed with the keyboard while it's focused
+#[derive(Debug, Default, Clone)]
+pub struct GpioPanel {
+    pub focused: bool,
+    /// the group of 8 switches the number keys toggle
+    bank: usize,
+}
+impl GpioPanel {
+    /// number keys toggle switches, arrows and enter hold or let go of the buttons
+    pub fn handle_key(&mut self, gpio: &mut Gpio, code: KeyCode) {
+        match code {
+            KeyCode::Esc | KeyCode::Char('o') => self.focused = false,
+            KeyCode::Tab => self.bank = (self.bank + 1) % (gpio::SWITCH_COUNT / SWITCHES_PER_BANK),
+            KeyCode::Char(character @ '1'..='8') => {
+                let switch = self.bank * SWITCHES_PER_BANK + character.to_digit(10).unwrap() as usize - 1;
+                gpio.inputs.toggle_switch(switch);
+            }
+            KeyCode::Up => gpio.inputs.toggle_button(0),
+            KeyCode::Enter => gpio.inputs.toggle_button(1),
+            KeyCode::Down => gpio.inputs.toggle_button(2),
+            KeyCode::Left => gpio.inputs.toggle_button(3),
+            KeyCode::Right => gpio.inputs.toggle_button(4),
+            _ => {}
+        }
+    }
+
+    pub fn widget(&self, gpio: &Gpio) -> Paragraph<'static> {
+        // highest bit on the left, like the numbers they're written from
+        let leds = |value: u32, count: usize| -> Vec<Span<'static>> {
+            let mut spans = Vec::new();
+            for led in (0..count).rev() {
+                spans.push(if value & (1 << led) != 0 { "●".light_green() } else { "○".dark_gray() });
+                if led % 8 == 0 && led != 0 {
+                    spans.push(" ".into());
+                }
+            }
+            spans
+        };
+
+        let mut board_line = vec!["LEDs     ".into()];
+        board_line.extend(leds(gpio.board_leds as u32, 8));
+        let mut io_line = vec!["Io LEDs  ".into()];
+        io_line.extend(leds(gpio.io_leds, gpio::IO_LED_COUNT));
+
+        let mut switch_line = vec!["Switches ".into()];
+        for switch in (0..gpio::SWITCH_COUNT).rev() {
+            let symbol = if gpio.inputs.switches & (1 << switch) != 0 { "▮" } else { "▯" };
+            let mut span = Span::from(symbol).yellow();
+            if self.focused && switch / SWITCHES_PER_BANK == self.bank {
+                span = span.on_dark_gray();
+            }
+            switch_line.push(span);
+            if switch % SWITCHES_PER_BANK == 0 && switch != 0 {
+                switch_line.push(" ".into());
+            }
+        }
+
+        let mut button_line = vec!["Buttons ".into()];
+        for (button, name) in gpio::BUTTON_NAMES.iter().enumerate() {
+            button_line.push(" ".into());
+            if gpio.inputs.buttons & (1 << button) != 0 {
+                button_line.push(name.to_string().black().on_yellow());
+            } else {
+                button_line.push(name.to_string().dark_gray());
+            }
+        }
+
+        let mut block = Block::bordered().title(" GPIO ");
+        if self.focused {
+            block = block
+                .title_bottom(Line::from(vec![
+                    " Switch ".into(), "<1-8>".blue().bold(),
+                    " Bank ".into(), "<Tab>".blue().bold(),
+                    " Buttons ".into(), "<Arrows/Enter>".blue().bold(),
+                    " Leave ".into(), "<Escape> ".blue().bold(),
+                ]).centered())
+                .border_style(Style::new().blue());
+        }
+        Paragraph::new(vec![Line::from(board_line), Line::from(io_line), Line::from(switch_line), Line::from(button_line)])
+            .block(block)
+    }
+}
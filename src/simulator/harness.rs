@@ -0,0 +1,335 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use serde::Deserialize;
+use crate::assembler;
+use crate::asm_parser::SourceTest;
+use crate::disassembler::symbols::SymbolTable;
+use crate::simulator::{load_binary, memory_view, watch, Options};
+use crate::simulator::devices::serial::SerialPort;
+use crate::simulator::executor::Processor;
+use crate::simulator::fault::FaultOutcome;
+use crate::simulator::headless::{END_LOOP_END, END_LOOP_START};
+use crate::simulator::watch::WatchTarget;
+
+/// how long a test can run for if it doesn't say
+const DEFAULT_MAX_CYCLES: u64 = 1_000_000;
+/// rts returns to 4 bytes after the address it pops (to skip over the jsr), so calls return into the end loop
+const CALL_RETURN_ADDRESS: u16 = END_LOOP_START - 4;
+
+/// a file of tests for one program (see doc/Testing.md)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TestFile {
+    /// source file to assemble, relative to the test file
+    pub program: Option<String>,
+    /// an already assembled binary (and its symbol table) instead of a source file
+    pub binary: Option<String>,
+    pub symbols: Option<String>,
+    pub rng_seed: Option<u16>,
+    #[serde(rename = "test", default)]
+    pub tests: Vec<TestCase>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TestCase {
+    pub name: String,
+    /// subroutine to call after the setup, the test ends when it returns
+    pub call: Option<String>,
+    /// address or label to stop at instead of the end loop
+    pub until: Option<String>,
+    pub max_cycles: Option<u64>,
+    /// registers and memory to set before running, in the same format as watches
+    #[serde(default)]
+    pub set: BTreeMap<String, Value>,
+    #[serde(default)]
+    pub serial_input: String,
+    /// registers and memory to check after running, in the same format as watches
+    #[serde(default)]
+    pub expect: BTreeMap<String, Value>,
+    /// everything the program should have sent over serial
+    pub serial_output: Option<String>,
+    pub cycles: Option<CycleExpectation>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Value {
+    /// a byte, or a 16 bit value for pairs and the program counter (negative numbers are two's complement)
+    Number(i64),
+    /// ascii, one byte per character
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum CycleExpectation {
+    Exact(u64),
+    Range { min: Option<u64>, max: Option<u64> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestResult {
+    pub name: String,
+    pub cycles: u64,
+    /// why it failed, empty if it passed
+    pub failures: Vec<String>,
+}
+impl TestResult {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// loads (or assembles) the program a test file is for and runs every test in it
+pub fn run_file(path: &str) -> Vec<TestResult> {
+    let text = std::fs::read_to_string(path).unwrap_or_else(|_| panic!("Test file not found ({path})!"));
+    let file: TestFile = toml::from_str(&text).unwrap_or_else(|error| panic!("Could not parse test file {path}: {error}"));
+    // paths in the file are relative to it
+    let relative = |file_path: &str| Path::new(path).parent().unwrap_or(Path::new(".")).join(file_path).to_string_lossy().to_string();
+    let (binary, symbol_table) = match (&file.program, &file.binary) {
+        (Some(program), None) => {
+            let (directory, filename) = assembler::split_path(&relative(program));
+            assembler::assemble_file(&directory, &filename, 65535)
+        }
+        (None, Some(binary)) => {
+            let binary_path = relative(binary);
+            let binary = std::fs::read(&binary_path).unwrap_or_else(|_| panic!("Binary not found ({binary_path})!"));
+            let symbol_table = file.symbols.as_ref().map(|symbols| {
+                let symbols_path = relative(symbols);
+                SymbolTable::from_bytes(&std::fs::read(&symbols_path).unwrap_or_else(|_| panic!("Symbol table not found ({symbols_path})!")))
+            }).unwrap_or_default();
+            (binary, symbol_table)
+        }
+        _ => panic!("Test file {path} needs either a program or a binary!"),
+    };
+    let options = Options {
+        rng_seed: file.rng_seed,
+        ..Options::default()
+    };
+    file.tests.iter().map(|case| run_case(&binary, &symbol_table, &options, case)).collect()
+}
+
+/// assembles a source file with its `.test` blocks and runs them
+/// includes are relative to the current directory, like they would be for a program in it
+pub fn run_source_file(path: &str) -> Vec<TestResult> {
+    let (binary, symbol_table, tests) = assembler::assemble_tests("./", path, 65535);
+    let options = Options::default();
+    tests.iter().map(|test| match source_test_case(test) {
+        Ok(case) => run_case(&binary, &symbol_table, &options, &case),
+        Err(error) => TestResult {
+            name: test.name.clone(),
+            cycles: 0,
+            failures: vec![error],
+        },
+    }).collect()
+}
+
+/// a `.test` block is a call to its subroutine, with its `.expect`s checked after it returns
+fn source_test_case(test: &SourceTest) -> Result<TestCase, String> {
+    let mut case = TestCase {
+        name: test.name.clone(),
+        call: Some(test.subroutine.clone()),
+        ..TestCase::default()
+    };
+    for expect in test.expects.iter() {
+        let (target, value) = expect.split_once(char::is_whitespace)
+            .ok_or_else(|| format!(".expect {expect} needs a value"))?;
+        let value = source_value(value.trim())?;
+        if target == "serial" {
+            let Value::Text(text) = value else {
+                return Err("The serial output should be a string".to_string());
+            };
+            if case.serial_output.replace(text).is_some() {
+                return Err("The serial output can only be expected once".to_string());
+            }
+        } else {
+            case.expect.insert(target.to_string(), value);
+        }
+    }
+    Ok(case)
+}
+
+/// an immediate (`#2A`, `#05DC`, `#^00101010`), or a string in double quotes
+fn source_value(text: &str) -> Result<Value, String> {
+    if let Some(string) = text.strip_prefix('"').and_then(|text| text.strip_suffix('"')) {
+        return unescape(string).map(Value::Text);
+    }
+    let number = text.strip_prefix('#').ok_or_else(|| format!("Expected an immediate or a string, got {text}"))?;
+    let parsed = match number.strip_prefix('^') {
+        Some(binary) => i64::from_str_radix(binary, 2),
+        None => i64::from_str_radix(number, 16),
+    };
+    parsed.map(Value::Number).map_err(|_| format!("{text} isn't a number"))
+}
+fn unescape(text: &str) -> Result<String, String> {
+    let mut result = String::with_capacity(text.len());
+    let mut characters = text.chars();
+    while let Some(character) = characters.next() {
+        if character != '\\' {
+            result.push(character);
+            continue;
+        }
+        match characters.next() {
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some('t') => result.push('\t'),
+            Some(escaped @ ('\\' | '"')) => result.push(escaped),
+            other => return Err(format!("Unknown escape \\{}", other.map(String::from).unwrap_or_default())),
+        }
+    }
+    Ok(result)
+}
+
+/// runs one test on a fresh cpu with the binary loaded
+pub fn run_case(binary: &[u8], symbol_table: &SymbolTable, options: &Options, case: &TestCase) -> TestResult {
+    let mut result = TestResult {
+        name: case.name.clone(),
+        cycles: 0,
+        failures: Vec::new(),
+    };
+    let mut device_options = options.open_devices();
+    device_options.serial.scripted_input = case.serial_input.as_bytes().to_vec();
+    let mut cpu = load_binary(binary, &device_options);
+
+    let stop_address = match set_up(&mut cpu, symbol_table, case) {
+        Ok(stop_address) => stop_address,
+        Err(error) => {
+            result.failures.push(error);
+            return result;
+        }
+    };
+    let serial_output = match run(&mut cpu, symbol_table, case, stop_address) {
+        Ok(serial_output) => serial_output,
+        Err(error) => {
+            result.cycles = cpu.cycles;
+            result.failures.push(error);
+            return result;
+        }
+    };
+    result.cycles = cpu.cycles;
+
+    // ------------------------------ ASSERTIONS ------------------------------
+    for (text, value) in case.expect.iter() {
+        let checked = watch::parse_watch(symbol_table, text).and_then(|watch| {
+            let expected = value_bytes(watch.target, value)?;
+            let actual = watch.target.read(&cpu, expected.len());
+            if actual != expected {
+                return Err(format!("{text} was {}, expected {}", describe(&actual), describe(&expected)));
+            }
+            Ok(())
+        });
+        if let Err(error) = checked {
+            result.failures.push(error);
+        }
+    }
+    if let Some(expected) = &case.serial_output {
+        let actual = String::from_utf8_lossy(&serial_output);
+        if actual != *expected {
+            result.failures.push(format!("Serial output was {actual:?}, expected {expected:?}"));
+        }
+    }
+    match case.cycles {
+        Some(CycleExpectation::Exact(expected)) if cpu.cycles != expected => {
+            result.failures.push(format!("Took {} cycles, expected {expected}", cpu.cycles));
+        }
+        Some(CycleExpectation::Range { min, max }) => {
+            if let Some(min) = min && cpu.cycles < min {
+                result.failures.push(format!("Took {} cycles, expected at least {min}", cpu.cycles));
+            }
+            if let Some(max) = max && cpu.cycles > max {
+                result.failures.push(format!("Took {} cycles, expected at most {max}", cpu.cycles));
+            }
+        }
+        _ => {}
+    }
+    // ------------------------------ END ASSERTIONS ------------------------------
+    result
+}
+
+/// sets the registers and memory, and sets up the call if there is one
+/// returns the address to stop at, or None to stop at the end loop
+fn set_up(cpu: &mut Processor, symbol_table: &SymbolTable, case: &TestCase) -> Result<Option<u16>, String> {
+    for (text, value) in case.set.iter() {
+        let watch = watch::parse_watch(symbol_table, text)?;
+        let bytes = value_bytes(watch.target, value)?;
+        watch.target.write(cpu, &bytes);
+    }
+    if let Some(name) = &case.call {
+        // subroutines are labels with _SR on the end
+        let address = symbol_table.resolve(&format!("{name}_SR"))
+            .map_or_else(|| memory_view::parse_address(symbol_table, name), Ok)
+            .map_err(|_| format!("Unknown subroutine \"{name}\""))?;
+        for byte in CALL_RETURN_ADDRESS.to_le_bytes() {
+            cpu.push_stack(byte);
+        }
+        cpu.program_counter = address;
+    }
+    case.until.as_ref().map(|until| memory_view::parse_address(symbol_table, until)).transpose()
+}
+
+/// runs until the stop address (or the end loop), returning everything sent over serial
+fn run(cpu: &mut Processor, symbol_table: &SymbolTable, case: &TestCase, stop_address: Option<u16>) -> Result<Vec<u8>, String> {
+    let max_cycles = case.max_cycles.unwrap_or(DEFAULT_MAX_CYCLES);
+    let mut serial_output = Vec::new();
+    let mut first_step = true;
+    loop {
+        let in_end_loop = (END_LOOP_START..=END_LOOP_END).contains(&cpu.program_counter);
+        if let Some(stop_address) = stop_address {
+            // the stop address can be where it starts, like the top of a loop
+            if cpu.program_counter == stop_address && !first_step {
+                return Ok(serial_output);
+            }
+            if in_end_loop {
+                return Err(format!("Finished without reaching {}", case.until.as_deref().unwrap_or_default()));
+            }
+        } else if in_end_loop {
+            // let whatever is left in the fifo get sent, like a headless run
+            let serial = cpu.bus.device::<SerialPort>().expect("The serial port should always be on the bus");
+            if !serial.is_sending() {
+                return Ok(serial_output);
+            }
+        }
+        if cpu.total_cycles >= max_cycles {
+            return Err(format!("Still running after {max_cycles} cycles (at {:04x})", cpu.program_counter));
+        }
+        cpu.step();
+        first_step = false;
+        // a reset starts the program over, which a test never means to do
+        if let Some(fault) = &cpu.fault && fault.outcome != FaultOutcome::Continued {
+            return Err(fault.report(symbol_table));
+        }
+        let serial = cpu.bus.device_mut::<SerialPort>().expect("The serial port should always be on the bus");
+        serial_output.append(&mut serial.take_output());
+    }
+}
+
+/// the bytes a value is for the target, high byte first
+fn value_bytes(target: WatchTarget, value: &Value) -> Result<Vec<u8>, String> {
+    let bytes = match value {
+        Value::Number(number) => match target.length().unwrap_or(1) {
+            1 if (-0x80..=0xFF).contains(number) => vec![*number as u8],
+            2 if (-0x8000..=0xFFFF).contains(number) => (*number as u16).to_be_bytes().to_vec(),
+            1 | 2 => return Err(format!("{number} is too big")),
+            length => return Err(format!("A number can't be used for {length} bytes, use a list or a string")),
+        },
+        Value::Text(text) => text.as_bytes().to_vec(),
+        Value::Bytes(bytes) => bytes.clone(),
+    };
+    match target.length() {
+        Some(length) if length != bytes.len() => Err(format!("Expected {length} bytes, got {}", bytes.len())),
+        _ if bytes.is_empty() => Err("Expected at least one byte".to_string()),
+        _ => Ok(bytes),
+    }
+}
+
+/// like `05dc (1500)`, or just hex for more than 2 bytes
+fn describe(bytes: &[u8]) -> String {
+    let hex = bytes.iter().map(|byte| format!("{byte:02x}")).collect::<Vec<String>>();
+    match bytes {
+        [byte] => format!("{byte:02x} ({byte})"),
+        [high, low] => format!("{high:02x}{low:02x} ({})", u16::from_be_bytes([*high, *low])),
+        _ => hex.join(" "),
+    }
+}
@@ -0,0 +1,98 @@
+use std::io::Write;
+use crossterm::style::Stylize;
+use crate::disassembler::symbols::SymbolTable;
+use crate::simulator::{load_processor, Options};
+use crate::simulator::devices::serial::SerialPort;
+use crate::simulator::fault::Fault;
+use crate::simulator::instruments::Instruments;
+
+/// the program loops here once it's done (see doc/GoldCore.md)
+pub const END_LOOP_START: u16 = 0xFFF8;
+pub const END_LOOP_END: u16 = 0xFFFB;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StopReason {
+    /// the program jumped to the infinite loop at the end of memory
+    EndLoop,
+    CycleLimit,
+    /// the program faulted and the policy for it is to halt
+    Fault(Fault),
+}
+
+/// runs the binary without the TUI, writing everything sent over serial to stdout
+/// returns why it stopped and the number of cycles that were run (including any before a reset)
+pub fn run(binary_path: &str, symbol_table: &SymbolTable, options: Options, max_cycles: Option<u64>) -> (StopReason, u64) {
+    let device_options = options.open_devices();
+    let mut instruments = Instruments::new(&options, symbol_table);
+    let mut cpu = load_processor(binary_path, &device_options);
+    let mut stdout = std::io::stdout().lock();
+    let stop_reason = loop {
+        let serial = cpu.bus.device::<SerialPort>().expect("The serial port should always be on the bus");
+        // the end loop keeps running on the real core, so whatever is left in the fifo still gets sent
+        if (END_LOOP_START..=END_LOOP_END).contains(&cpu.program_counter) && !serial.is_sending() {
+            break StopReason::EndLoop;
+        }
+        if let Some(max_cycles) = max_cycles
+            && cpu.total_cycles >= max_cycles
+        {
+            break StopReason::CycleLimit;
+        }
+        instruments.step(&mut cpu);
+        if let Some(fault) = &cpu.fault {
+            if fault.halted() {
+                break StopReason::Fault(fault.clone());
+            }
+            // the other policies keep going, but it's still worth knowing about
+            eprintln!("{}", format!("WARNING: {}", fault.report(symbol_table)).yellow());
+        }
+
+        let serial = cpu.bus.device_mut::<SerialPort>().expect("The serial port should always be on the bus");
+        let output = serial.take_output();
+        if !output.is_empty() {
+            stdout.write_all(&output).expect("Failed to write serial output to stdout!");
+            stdout.flush().expect("Failed to write serial output to stdout!");
+        }
+    };
+    instruments.finish(&cpu);
+    (stop_reason, cpu.total_cycles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::fault::StackPolicy;
+
+    /// writes a binary with the code at 0200 (where the reset vector points) to a temporary file, returning its path
+    fn write_binary(name: &str, code: &[u8]) -> String {
+        let mut binary = vec![0; 0x10000];
+        binary[0x0200..0x0200 + code.len()].copy_from_slice(code);
+        binary[0xFFFC] = 0x02;
+        let path = std::env::temp_dir().join(format!("goldasm_{name}_{}.bin", std::process::id()));
+        std::fs::write(&path, binary).expect("Failed to write the test binary");
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn stack_reset_stops_at_the_cycle_limit() {
+        // plr 00 with nothing on the stack underflows, which resets straight back to it
+        let path = write_binary("stack_reset", &[0x22, 0x00]);
+        let options = Options {
+            stack_policy: StackPolicy::Reset,
+            ..Options::default()
+        };
+        let (stop_reason, cycles) = run(&path, &SymbolTable::default(), options, Some(100));
+        std::fs::remove_file(&path).ok();
+        assert_eq!(stop_reason, StopReason::CycleLimit);
+        assert!(cycles >= 100);
+    }
+
+    #[test]
+    fn invalid_opcode_reset_stops_at_the_cycle_limit() {
+        // ff is never an opcode, and resetting is the default
+        let path = write_binary("opcode_reset", &[0xFF]);
+        let (stop_reason, cycles) = run(&path, &SymbolTable::default(), Options::default(), Some(100));
+        std::fs::remove_file(&path).ok();
+        assert_eq!(stop_reason, StopReason::CycleLimit);
+        assert!(cycles >= 100);
+    }
+}
@@ -0,0 +1,66 @@
+use crate::disassembler::symbols::SymbolTable;
+use crate::simulator::Options;
+use crate::simulator::coverage::Coverage;
+use crate::simulator::executor::Processor;
+use crate::simulator::fault::FaultOutcome;
+use crate::simulator::profiler::Profiler;
+use crate::simulator::trace::Tracer;
+
+/// everything that watches the program run, set up from the command line
+#[derive(Debug, Default)]
+pub struct Instruments {
+    pub tracer: Option<Tracer>,
+    pub profiler: Option<Profiler>,
+    pub coverage: Option<Coverage>,
+}
+impl Instruments {
+    pub fn new(options: &Options, symbol_table: &SymbolTable) -> Instruments {
+        Instruments {
+            tracer: options.trace.as_ref().map(|trace_options| Tracer::create(trace_options, symbol_table)),
+            profiler: options.profile.as_ref().map(|profile_options| Profiler::new(profile_options, symbol_table)),
+            coverage: options.coverage.as_ref().map(|coverage_options| Coverage::new(coverage_options, symbol_table)),
+        }
+    }
+
+    /// steps the cpu, letting every instrument see the instruction
+    pub fn step(&mut self, cpu: &mut Processor) {
+        if let Some(profiler) = &mut self.profiler {
+            profiler.before_step(cpu);
+        }
+        if let Some(coverage) = &mut self.coverage {
+            coverage.before_step(cpu);
+        }
+        match &mut self.tracer {
+            Some(tracer) => tracer.step(cpu),
+            None => cpu.step(),
+        }
+        if let Some(profiler) = &mut self.profiler {
+            profiler.after_step(cpu);
+        }
+        if let Some(coverage) = &mut self.coverage {
+            coverage.after_step(cpu);
+        }
+        if cpu.fault.as_ref().is_some_and(|fault| fault.outcome == FaultOutcome::Reset) {
+            self.reset();
+        }
+    }
+
+    pub fn reset(&mut self) {
+        if let Some(tracer) = &mut self.tracer {
+            tracer.mark_reset();
+        }
+        if let Some(profiler) = &mut self.profiler {
+            profiler.reset();
+        }
+    }
+
+    /// writes out anything that's only written at the end of a run
+    pub fn finish(&mut self, cpu: &Processor) {
+        if let Some(profiler) = &self.profiler {
+            profiler.write(cpu);
+        }
+        if let Some(coverage) = &self.coverage {
+            coverage.write(cpu);
+        }
+    }
+}
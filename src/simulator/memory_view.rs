@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Paragraph};
+use crate::disassembler::symbols::SymbolTable;
+use crate::simulator::executor::Processor;
+
+/// number of steps that a write stays highlighted for
+const HIGHLIGHT_STEPS: u64 = 16;
+const BYTES_PER_ROW: u16 = 16;
+
+#[derive(Debug, Default, Clone)]
+pub struct MemoryView {
+    pub cursor: u16,
+    pub focused: bool,
+    // the row (not the address) at the top of the pane
+    top_row: u16,
+    // the first hex digit typed while editing the byte under the cursor
+    pending_nibble: Option<u8>,
+    // address -> step it was last written on
+    recent_writes: HashMap<u16, u64>,
+    steps: u64,
+}
+impl MemoryView {
+    pub fn record_step(&mut self, writes: &[(u16, u8)]) {
+        self.steps += 1;
+        for (address, _) in writes {
+            self.recent_writes.insert(*address, self.steps);
+        }
+        let steps = self.steps;
+        self.recent_writes.retain(|_, written_at| steps - *written_at < HIGHLIGHT_STEPS);
+    }
+    pub fn record_write(&mut self, address: u16) {
+        self.recent_writes.insert(address, self.steps);
+    }
+    pub fn clear_history(&mut self) {
+        self.recent_writes.clear();
+        self.pending_nibble = None;
+        self.steps = 0;
+    }
+    pub fn goto(&mut self, address: u16) {
+        self.cursor = address;
+        self.pending_nibble = None;
+    }
+    pub fn move_cursor(&mut self, offset: i32) {
+        self.cursor = (self.cursor as i32 + offset).rem_euclid(0x10000) as u16;
+        self.pending_nibble = None;
+    }
+    /// types one hex digit into the byte under the cursor, returns true once the byte has been written
+    pub fn type_hex_digit(&mut self, cpu: &mut Processor, digit: u8) -> bool {
+        if let Some(high_nibble) = self.pending_nibble.take() {
+            cpu.bus.write(self.cursor, (high_nibble << 4) | digit);
+            self.record_write(self.cursor);
+            self.move_cursor(1);
+            true
+        } else {
+            self.pending_nibble = Some(digit);
+            false
+        }
+    }
+
+    pub fn widget(&mut self, cpu: &Processor, symbol_table: &SymbolTable, height: u16) -> Paragraph<'static> {
+        // keep the cursor on screen
+        let visible_rows = height.saturating_sub(2).max(1);
+        let cursor_row = self.cursor / BYTES_PER_ROW;
+        if cursor_row < self.top_row {
+            self.top_row = cursor_row;
+        } else if cursor_row >= self.top_row + visible_rows {
+            self.top_row = cursor_row - visible_rows + 1;
+        }
+        let last_row = 0x10000 / BYTES_PER_ROW as u32;
+        let end_row = (self.top_row as u32 + visible_rows as u32).min(last_row);
+
+        let mut lines = Vec::with_capacity(visible_rows as usize);
+        for row in self.top_row as u32..end_row {
+            let row_address = (row * BYTES_PER_ROW as u32) as u16;
+            let mut spans = vec![format!("0x{row_address:04x}: ").yellow()];
+            let mut ascii_spans = vec![" ".into()];
+            for column in 0..BYTES_PER_ROW {
+                let address = row_address + column;
+                let value = cpu.bus.peek(address);
+                let style = self.byte_style(address, cpu.program_counter);
+
+                let hex = if address == self.cursor && let Some(high_nibble) = self.pending_nibble {
+                    format!("{high_nibble:x}_")
+                } else {
+                    format!("{value:02x}")
+                };
+                spans.push(Span::styled(hex, style));
+                spans.push(" ".into());
+
+                let character = if value.is_ascii_graphic() || value == b' ' { value as char } else { '.' };
+                ascii_spans.push(Span::styled(character.to_string(), style));
+            }
+            spans.append(&mut ascii_spans);
+            lines.push(Line::from(spans));
+        }
+
+        // ------------------------------ CURSOR INFO ------------------------------
+        let cursor_value = cpu.bus.peek(self.cursor);
+        let mut cursor_info = format!(" 0x{:04x} = 0x{cursor_value:02x} ({cursor_value}) ", self.cursor);
+        if let Some(define) = symbol_table.define_at(self.cursor) {
+            cursor_info += &format!("*{define} ");
+        }
+
+        let mut block = Block::bordered()
+            .title(" Memory ")
+            .title_bottom(Line::from(cursor_info).right_aligned());
+        if self.focused {
+            block = block
+                .title(Line::from(" (editing - type hex to overwrite, <Escape> to leave) ".blue().bold()))
+                .border_style(Style::new().blue());
+        }
+        Paragraph::new(lines).block(block)
+    }
+
+    fn byte_style(&self, address: u16, program_counter: u16) -> Style {
+        let mut style = Style::new().green();
+        if address == program_counter {
+            style = style.light_green().bold();
+        }
+        if let Some(written_at) = self.recent_writes.get(&address) {
+            if self.steps - written_at == 0 {
+                style = style.red().bold();
+            } else {
+                style = style.light_red();
+            }
+        }
+        if address == self.cursor {
+            style = style.reversed();
+        }
+        style
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PromptKind {
+    Goto,
+    Write,
+    /// scrolls the disassembly instead of the memory pane
+    Disassembly,
+    /// adds to the watches
+    Watch,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Prompt {
+    pub kind: PromptKind,
+    pub input: String,
+    pub error: Option<String>,
+}
+impl Prompt {
+    pub fn new(kind: PromptKind) -> Prompt {
+        Prompt {
+            kind,
+            input: String::new(),
+            error: None,
+        }
+    }
+    pub fn title(&self) -> &'static str {
+        match self.kind {
+            PromptKind::Goto => " Go to an address or symbol (FF00, bank.p1_score_low, ~LOOP) ",
+            PromptKind::Watch => " Watch a register, address, pair, or range (r3, math.RESULT_LOW, RESULT_HIGH:RESULT_LOW, 0300-030F) ",
+            PromptKind::Disassembly => " Show the disassembly at an address or symbol (0200, ~LOOP) ",
+            PromptKind::Write => " Write a value (r3 = 10, a = FF, carry = 1, flag.i = 0, pc = 0200, FF00 = 41, *RESULT_LOW = ^101) ",
+        }
+    }
+}
+
+/// parses an address, which is either hex (with an optional % or 0x) or the name of a define or label
+/// names can be forced with a * or ~ in front of them, in case they look like hex
+pub fn parse_address(symbol_table: &SymbolTable, text: &str) -> Result<u16, String> {
+    let text = text.trim();
+    if let Some(name) = text.strip_prefix('*').or(text.strip_prefix('~')) {
+        return symbol_table.resolve(name).ok_or(format!("Unknown symbol \"{name}\""));
+    }
+    let hex = text.strip_prefix('%').or(text.strip_prefix("0x")).unwrap_or(text);
+    if !hex.is_empty() && hex.len() <= 4
+        && let Ok(address) = u16::from_str_radix(hex, 16)
+    {
+        return Ok(address);
+    }
+    symbol_table.resolve(text).ok_or(format!("\"{text}\" is not an address or a known symbol"))
+}
+
+/// parses a value in the same format as the assembler (hex by default, ^ for binary, optional #)
+fn parse_value(text: &str) -> Result<u16, String> {
+    let text = text.trim();
+    let text = text.strip_prefix('#').unwrap_or(text);
+    let parsed = if let Some(binary) = text.strip_prefix('^') {
+        u16::from_str_radix(binary, 2)
+    } else {
+        u16::from_str_radix(text.strip_prefix("0x").unwrap_or(text), 16)
+    };
+    parsed.map_err(|_| format!("\"{text}\" is not a valid value"))
+}
+
+fn parse_byte(text: &str) -> Result<u8, String> {
+    let value = parse_value(text)?;
+    u8::try_from(value).map_err(|_| format!("{value:x} does not fit in a byte"))
+}
+
+/// applies a write like `r3 = 10` to the cpu
+/// returns the memory address that was written, if the target was memory
+pub fn apply_write(cpu: &mut Processor, symbol_table: &SymbolTable, input: &str) -> Result<Option<u16>, String> {
+    let (target, value) = input.split_once('=')
+        .or(input.trim().split_once(char::is_whitespace))
+        .ok_or("Expected \"target = value\"".to_string())?;
+    let target = target.trim();
+    let value = value.trim();
+
+    // a flag's first letter needs the prefix, since c and e on their own are addresses
+    let flag = match target.to_lowercase().as_str() {
+        "flag.c" | "carry" => Some(0b100000_00),
+        "flag.z" | "zero" => Some(0b010000_00),
+        "flag.g" | "greater" => Some(0b001000_00),
+        "flag.l" | "less" => Some(0b000100_00),
+        "flag.e" | "equal" => Some(0b000010_00),
+        "flag.n" | "negative" => Some(0b000001_00),
+        "flag.i" | "interrupt" => Some(0b000000_10),
+        _ => None,
+    };
+    if let Some(flag) = flag {
+        match parse_value(value)? {
+            0 => cpu.status_register &= !flag,
+            1 => cpu.status_register |= flag,
+            _ => return Err("Flags can only be set to 0 or 1".to_string()),
+        }
+        return Ok(None);
+    }
+
+    let register = target.to_lowercase().strip_prefix('r')
+        .and_then(|index| index.parse::<usize>().ok())
+        .filter(|index| *index < cpu.registers.len());
+    if let Some(register) = register {
+        cpu.registers[register] = parse_byte(value)?;
+        return Ok(None);
+    }
+
+    match target.to_lowercase().as_str() {
+        "a" | "acc" | "accumulator" => cpu.accumulator = parse_byte(value)?,
+        "sr" | "status" => cpu.status_register = parse_byte(value)?,
+        "sp" => cpu.stack_pointer = parse_byte(value)?,
+        "pc" => cpu.program_counter = parse_value(value)?,
+        _ => {
+            let address = parse_address(symbol_table, target)?;
+            cpu.bus.write(address, parse_byte(value)?);
+            return Ok(Some(address));
+        }
+    }
+    Ok(None)
+}
@@ -0,0 +1,179 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use crate::disassembler;
+use crate::disassembler::symbols::SymbolTable;
+use crate::simulator::{bin_parser, call_stack};
+use crate::simulator::call_stack::{CallChange, CallTracker};
+use crate::simulator::executor::Processor;
+use crate::simulator::trace::Labels;
+
+/// name of the frame at the bottom of every stack
+const ROOT: &str = "main";
+/// number of addresses listed in the report
+const HOT_ADDRESSES: usize = 50;
+
+/// where to write the profile, from the command line
+#[derive(Debug, Default, Clone)]
+pub struct ProfileOptions {
+    /// the sorted text report
+    pub report: Option<String>,
+    /// folded stacks (`main;serial_out.SEND_BYTE 1234`) for flamegraph tools
+    pub folded: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct SubroutineStats {
+    calls: u64,
+    self_cycles: u64,
+    /// cycles spent in it or anything it called
+    inclusive_cycles: u64,
+}
+
+/// counts where the program spends its time, both by address and by subroutine
+/// subroutines are followed as they run (a jsr pushes a frame and an rts pops one), and the jsr belongs to the caller
+#[derive(Debug)]
+pub struct Profiler {
+    options: ProfileOptions,
+    symbol_table: SymbolTable,
+    labels: Labels,
+    executions: Box<[u64; 65536]>,
+    cycles: Box<[u64; 65536]>,
+    subroutines: HashMap<String, SubroutineStats>,
+    /// folded stack -> cycles spent with exactly that stack
+    folded: HashMap<String, u64>,
+    stack: Vec<String>,
+    /// the stack joined with ;
+    stack_key: String,
+    // the instruction that's running
+    program_counter: u16,
+    opcode: u8,
+    cycles_before: u64,
+    calls: CallTracker,
+    total_cycles: u64,
+    total_instructions: u64,
+}
+impl Profiler {
+    pub fn new(options: &ProfileOptions, symbol_table: &SymbolTable) -> Profiler {
+        Profiler {
+            options: options.clone(),
+            symbol_table: symbol_table.clone(),
+            labels: Labels::new(symbol_table),
+            executions: Box::new([0; 65536]),
+            cycles: Box::new([0; 65536]),
+            subroutines: HashMap::new(),
+            folded: HashMap::new(),
+            stack: vec![ROOT.to_string()],
+            stack_key: ROOT.to_string(),
+            program_counter: 0,
+            opcode: 0,
+            cycles_before: 0,
+            calls: CallTracker::default(),
+            total_cycles: 0,
+            total_instructions: 0,
+        }
+    }
+
+    pub fn before_step(&mut self, cpu: &Processor) {
+        self.program_counter = cpu.program_counter;
+        self.opcode = cpu.bus.memory[cpu.program_counter as usize];
+        // cycles is cleared by a reset
+        self.cycles_before = cpu.total_cycles;
+    }
+    pub fn after_step(&mut self, cpu: &Processor) {
+        // the instruction didn't run
+        if cpu.fault.as_ref().is_some_and(|fault| fault.halted()) {
+            return;
+        }
+        let cost = cpu.total_cycles - self.cycles_before;
+        self.executions[self.program_counter as usize] += 1;
+        self.cycles[self.program_counter as usize] += cost;
+        self.total_cycles += cost;
+        self.total_instructions += 1;
+
+        *self.folded.entry(self.stack_key.clone()).or_default() += cost;
+        let mut counted = HashSet::with_capacity(self.stack.len());
+        for name in self.stack.iter() {
+            // recursive calls only count once
+            if counted.insert(name) {
+                self.subroutines.entry(name.clone()).or_default().inclusive_cycles += cost;
+            }
+        }
+        let top = self.stack.last().expect("The root frame is never popped").clone();
+        self.subroutines.entry(top).or_default().self_cycles += cost;
+
+        for change in self.calls.step(self.opcode, cpu).into_iter().flatten() {
+            match change {
+                CallChange::Call(target) => {
+                    let name = call_stack::subroutine_name(&self.symbol_table, target);
+                    self.subroutines.entry(name.clone()).or_default().calls += 1;
+                    self.stack_key += &format!(";{name}");
+                    self.stack.push(name);
+                }
+                CallChange::Return if self.stack.len() > 1 => {
+                    self.stack.pop();
+                    self.stack_key = self.stack.join(";");
+                }
+                CallChange::Return => {}
+            }
+        }
+    }
+
+    /// the counts are kept across resets, but the program starts over at the root
+    pub fn reset(&mut self) {
+        self.stack.truncate(1);
+        self.stack_key = ROOT.to_string();
+        self.calls.reset();
+    }
+
+    /// writes the report and folded stacks, memory is used to disassemble the hot addresses
+    pub fn write(&self, cpu: &Processor) {
+        if let Some(path) = &self.options.report {
+            let mut writer = create(path);
+            self.write_report(&mut writer, cpu).unwrap_or_else(|error| panic!("Failed to write the profile ({error})!"));
+        }
+        if let Some(path) = &self.options.folded {
+            let mut writer = create(path);
+            let mut stacks = self.folded.iter().filter(|(_, cycles)| **cycles > 0).collect::<Vec<_>>();
+            stacks.sort();
+            for (stack, cycles) in stacks {
+                writeln!(writer, "{stack} {cycles}").unwrap_or_else(|error| panic!("Failed to write the folded stacks ({error})!"));
+            }
+        }
+    }
+
+    fn write_report(&self, writer: &mut impl Write, cpu: &Processor) -> std::io::Result<()> {
+        let percent = |cycles: u64| cycles as f64 * 100.0 / self.total_cycles.max(1) as f64;
+        writeln!(writer, "# {} cycles, {} instructions", self.total_cycles, self.total_instructions)?;
+
+        writeln!(writer, "\n# subroutines by inclusive cycles")?;
+        writeln!(writer, "{:>8} {:>12} {:>8} {:>12} {:>8}  name", "incl %", "inclusive", "self %", "self", "calls")?;
+        let mut subroutines = self.subroutines.iter().collect::<Vec<_>>();
+        subroutines.sort_by(|(a_name, a), (b_name, b)| b.inclusive_cycles.cmp(&a.inclusive_cycles).then(a_name.cmp(b_name)));
+        for (name, stats) in subroutines {
+            writeln!(writer, "{:>7.2}% {:>12} {:>7.2}% {:>12} {:>8}  {name}",
+                     percent(stats.inclusive_cycles), stats.inclusive_cycles,
+                     percent(stats.self_cycles), stats.self_cycles, stats.calls)?;
+        }
+
+        writeln!(writer, "\n# hottest addresses by cycles")?;
+        writeln!(writer, "{:>8} {:>12} {:>12}  address  {:<22} symbol", "%", "cycles", "executions", "instruction")?;
+        let mut addresses = (0..=0xFFFFu16).filter(|address| self.executions[*address as usize] > 0).collect::<Vec<u16>>();
+        addresses.sort_by_key(|address| std::cmp::Reverse(self.cycles[*address as usize]));
+        for address in addresses.into_iter().take(HOT_ADDRESSES) {
+            let instruction = match bin_parser::parse_instruction(&cpu.bus.memory, address) {
+                Ok((instruction, extra_bytes)) => disassembler::disassemble(vec![instruction], vec![extra_bytes]).remove(0),
+                Err(_) => "???".to_string(),
+            };
+            let cycles = self.cycles[address as usize];
+            writeln!(writer, "{:>7.2}% {cycles:>12} {:>12}  {address:04x}     {instruction:<22} {}",
+                     percent(cycles), self.executions[address as usize], self.labels.name_at(address))?;
+        }
+        Ok(())
+    }
+}
+
+fn create(path: &str) -> BufWriter<File> {
+    let file = File::create(path).unwrap_or_else(|error| panic!("Could not create \"{path}\" ({error})!"));
+    BufWriter::new(file)
+}
@@ -0,0 +1,84 @@
+use crate::simulator::bin_parser;
+use crate::simulator::bin_parser::Instruction;
+
+/// the clock on the Alchitry Au, which the core is assumed to run at, for devices that have timings in real time
+pub const CLOCK_HZ: u64 = 100_000_000;
+
+/// clock cycles it takes to start an interrupt: pushing the program counter and status register, then reading the vector
+pub const INTERRUPT_CYCLES: u64 = 6;
+
+/// how many clock cycles each opcode takes
+#[derive(Debug, Clone, PartialEq)]
+pub struct CycleTable {
+    cycles: [u8; 256],
+}
+impl Default for CycleTable {
+    /// an estimate until the hardware is measured: one cycle for every byte of the instruction,
+    /// plus one for every byte it reads or writes (including the stack)
+    fn default() -> Self {
+        let mut cycles = [1; 256];
+        let mut memory = Box::new([0; 65536]);
+        for opcode in 0..=255u8 {
+            memory[0] = opcode;
+            // operands are all zero, which is valid for every instruction
+            if let Ok((instruction, extra_bytes)) = bin_parser::parse_instruction(&memory, 0) {
+                cycles[opcode as usize] = 1 + extra_bytes + data_accesses(&instruction);
+            }
+        }
+        CycleTable { cycles }
+    }
+}
+impl CycleTable {
+    /// reads a table from a file, which changes the defaults for the opcodes it lists
+    /// each line is `OPCODE CYCLES` with the opcode in hex (`27 2`), and # starts a comment
+    pub fn load(path: &str) -> CycleTable {
+        let content = std::fs::read_to_string(path).unwrap_or_else(|_| panic!("Cycle table not found ({path})!"));
+        let mut table = CycleTable::default();
+        for (index, line) in content.lines().enumerate() {
+            let line = line.split_once('#').map_or(line, |(line, _)| line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            let words = line.split_whitespace().collect::<Vec<&str>>();
+            let parsed = match words.as_slice() {
+                [opcode, cycles] => {
+                    let opcode = opcode.strip_prefix("0x").unwrap_or(opcode);
+                    u8::from_str_radix(opcode, 16).ok().zip(cycles.parse::<u8>().ok().filter(|cycles| *cycles > 0))
+                }
+                _ => None,
+            };
+            let Some((opcode, cycles)) = parsed else {
+                panic!("Line {} of the cycle table ({path}) should be an opcode in hex and a number of cycles (got \"{line}\")!", index + 1);
+            };
+            table.cycles[opcode as usize] = cycles;
+        }
+        table
+    }
+    pub fn cycles(&self, opcode: u8) -> u64 {
+        self.cycles[opcode as usize] as u64
+    }
+}
+
+fn data_accesses(instruction: &Instruction) -> u8 {
+    match instruction {
+        Instruction::LoadAccumulator(Some(_), _) => 1,
+        Instruction::StoreAccumulator(_) => 1,
+        Instruction::PushRegisterToStack(_) | Instruction::PopRegisterFromStack(_) => 1,
+        Instruction::PushProgramCounter | Instruction::PopProgramCounter | Instruction::PopProgramCounterSubroutine => 2,
+        Instruction::ReturnFromInterrupt => 3,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn register_indexed_branches_take_a_cycle_per_byte() {
+        let table = CycleTable::default();
+        // beq and bne with a register and an indexed address are 5 bytes, and don't read or write memory
+        assert_eq!(table.cycles(0x4B), 5);
+        assert_eq!(table.cycles(0x4D), 5);
+    }
+}
@@ -0,0 +1,189 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use crate::disassembler;
+use crate::disassembler::symbols::{SymbolTable, SymbolType};
+use crate::simulator::bin_parser;
+use crate::simulator::executor::{Interrupt, Processor};
+
+const FLAG_NAMES: [(char, u8); 7] = [
+    ('c', 0b100000_00),
+    ('z', 0b010000_00),
+    ('g', 0b001000_00),
+    ('l', 0b000100_00),
+    ('e', 0b000010_00),
+    ('n', 0b000001_00),
+    ('i', 0b000000_10),
+];
+
+/// what to trace, from the command line
+#[derive(Debug, Default, Clone)]
+pub struct TraceOptions {
+    pub path: String,
+    /// inclusive address ranges, only instructions in them are traced (everything is if there are no ranges or subroutines)
+    pub ranges: Vec<(u16, u16)>,
+    /// subroutine names, only instructions in their bodies are traced (not the subroutines they call)
+    pub subroutines: Vec<String>,
+}
+
+/// parses a range like `0200-02FF` (or a single address)
+pub fn parse_range(text: &str) -> Result<(u16, u16), String> {
+    let (start, end) = text.split_once('-').unwrap_or((text, text));
+    let parse = |address: &str| {
+        let address = address.trim();
+        let address = address.strip_prefix('%').or(address.strip_prefix("0x")).unwrap_or(address);
+        u16::from_str_radix(address, 16).map_err(|_| format!("\"{text}\" is not a range of hex addresses (like 0200-02FF)"))
+    };
+    let (start, end) = (parse(start)?, parse(end)?);
+    if start > end {
+        return Err(format!("The range \"{text}\" is backwards"));
+    }
+    Ok((start, end))
+}
+
+/// names addresses by the label at or before them
+#[derive(Debug, Default, Clone)]
+pub struct Labels {
+    /// sorted by address
+    labels: Vec<(u16, String)>,
+}
+impl Labels {
+    pub fn new(symbol_table: &SymbolTable) -> Labels {
+        let mut labels = symbol_table.symbols.iter()
+            .filter(|(_, symbol)| symbol.symbol_type == SymbolType::Label)
+            .map(|(address, symbol)| (*address, symbol.name.rsplit_once('/').unwrap_or(("", &symbol.name)).1.to_string()))
+            .collect::<Vec<(u16, String)>>();
+        labels.sort();
+        Labels { labels }
+    }
+    /// like `serialtest.LOOP+3`, or nothing if there are no labels before the address
+    pub fn name_at(&self, address: u16) -> String {
+        let index = self.labels.partition_point(|(label_address, _)| *label_address <= address);
+        match index.checked_sub(1).map(|index| &self.labels[index]) {
+            Some((label_address, name)) if *label_address == address => name.clone(),
+            Some((label_address, name)) => format!("{name}+{:x}", address - label_address),
+            None => String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct RegisterState {
+    accumulator: u8,
+    registers: [u8; 8],
+    status_register: u8,
+    stack_pointer: u8,
+}
+impl RegisterState {
+    fn of(cpu: &Processor) -> RegisterState {
+        RegisterState {
+            accumulator: cpu.accumulator,
+            registers: cpu.registers,
+            status_register: cpu.status_register,
+            stack_pointer: cpu.stack_pointer,
+        }
+    }
+}
+
+/// writes one line per executed instruction
+/// (`cycle pc bytes instruction symbol changes`, where changes are registers, flags, sp, and memory writes)
+#[derive(Debug)]
+pub struct Tracer {
+    writer: BufWriter<File>,
+    ranges: Vec<(u16, u16)>,
+    labels: Labels,
+}
+impl Tracer {
+    pub fn create(options: &TraceOptions, symbol_table: &SymbolTable) -> Tracer {
+        let mut ranges = options.ranges.clone();
+        for name in options.subroutines.iter() {
+            let start = symbol_table.resolve(&format!("{name}_SR"));
+            // the end label is stored at the rts
+            let end = symbol_table.resolve(&format!("{name}_EndSR"));
+            match (start, end) {
+                (Some(start), Some(end)) if start <= end => ranges.push((start, end)),
+                _ => panic!("Could not find the subroutine \"{name}\" to trace (is the symbol table loaded?)!"),
+            }
+        }
+
+        let file = File::create(&options.path).unwrap_or_else(|error| panic!("Could not create trace \"{}\" ({error})!", options.path));
+        let mut tracer = Tracer {
+            writer: BufWriter::new(file),
+            ranges,
+            labels: Labels::new(symbol_table),
+        };
+        tracer.write_line("# cycle pc bytes instruction symbol changes");
+        tracer
+    }
+    pub fn mark_reset(&mut self) {
+        self.write_line("# reset");
+    }
+
+    /// steps the cpu, tracing the instruction if it passes the filters
+    pub fn step(&mut self, cpu: &mut Processor) {
+        let program_counter = cpu.program_counter;
+        if !self.ranges.is_empty() && !self.ranges.iter().any(|(start, end)| (*start..=*end).contains(&program_counter)) {
+            cpu.step();
+            return;
+        }
+        let before = RegisterState::of(cpu);
+        let (disassembly, length) = match bin_parser::parse_instruction(&cpu.bus.memory, program_counter) {
+            Ok((instruction, extra_bytes)) => {
+                let disassembly = disassembler::disassemble(vec![instruction], vec![extra_bytes]).remove(0);
+                (disassembly, extra_bytes as u16 + 1)
+            }
+            Err(_) => ("???".to_string(), 1),
+        };
+        let bytes = (0..length)
+            .map(|offset| format!("{:02x}", cpu.bus.memory[program_counter.wrapping_add(offset) as usize]))
+            .collect::<Vec<String>>()
+            .join(" ");
+        let symbol = self.labels.name_at(program_counter);
+
+        cpu.step();
+
+        let changes = changes(&before, &RegisterState::of(cpu), cpu);
+        let line = format!("{:>8} {program_counter:04x}  {bytes:<12} {disassembly:<22} {symbol:<32} {changes}", cpu.cycles);
+        self.write_line(line.trim_end());
+    }
+
+    fn write_line(&mut self, line: &str) {
+        // not flushed every line because traces are huge, the writer is flushed when it's dropped
+        writeln!(self.writer, "{line}").expect("Failed to write to the trace!");
+    }
+}
+
+fn changes(before: &RegisterState, after: &RegisterState, cpu: &Processor) -> String {
+    let mut changes = Vec::new();
+    if before.accumulator != after.accumulator {
+        changes.push(format!("a={:02x}", after.accumulator));
+    }
+    for (index, (old, new)) in before.registers.iter().zip(after.registers.iter()).enumerate() {
+        if old != new {
+            changes.push(format!("r{index}={new:02x}"));
+        }
+    }
+    if before.status_register != after.status_register {
+        let flags = FLAG_NAMES.iter()
+            .filter_map(|(name, mask)| {
+                match (before.status_register & mask != 0, after.status_register & mask != 0) {
+                    (false, true) => Some(format!("+{name}")),
+                    (true, false) => Some(format!("-{name}")),
+                    _ => None,
+                }
+            })
+            .collect::<String>();
+        changes.push(format!("sr={:02x}({flags})", after.status_register));
+    }
+    if before.stack_pointer != after.stack_pointer {
+        changes.push(format!("sp={:02x}", after.stack_pointer));
+    }
+    for (address, value) in cpu.bus.writes.iter() {
+        changes.push(format!("[{address:04x}]={value:02x}"));
+    }
+    match cpu.interrupt {
+        Some(Interrupt::Irq) => changes.push("irq".to_string()),
+        Some(Interrupt::Nmi) => changes.push("nmi".to_string()),
+        None => {}
+    }
+    changes.join(" ")
+}
@@ -0,0 +1,122 @@
+use crate::disassembler::symbols::SymbolTable;
+use crate::simulator::executor::Processor;
+use crate::simulator::memory_view;
+
+/// the most bytes a range can watch, so it fits in the pane
+const MAX_RANGE_LENGTH: u16 = 0x40;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Register {
+    Accumulator,
+    General(usize),
+    Status,
+    StackPointer,
+    ProgramCounter,
+}
+impl Register {
+    pub fn parse(text: &str) -> Option<Register> {
+        match text.to_lowercase().as_str() {
+            "a" | "acc" | "accumulator" => Some(Register::Accumulator),
+            "sr" | "status" => Some(Register::Status),
+            "sp" => Some(Register::StackPointer),
+            "pc" => Some(Register::ProgramCounter),
+            text => text.strip_prefix('r')
+                .and_then(|index| index.parse::<usize>().ok())
+                .filter(|index| *index < 8)
+                .map(Register::General),
+        }
+    }
+}
+
+/// what a watch looks at
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WatchTarget {
+    Register(Register),
+    Byte(u16),
+    /// a 16 bit value split over two addresses, high byte first
+    Pair { high: u16, low: u16 },
+    /// every byte from the start to the end (inclusive)
+    Range(u16, u16),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Watch {
+    /// what was typed, shown as the name of the watch
+    pub text: String,
+    pub target: WatchTarget,
+}
+
+/// parses a watch expression: a register (`r3`, `a`, `pc`), an address or define (`math.RESULT_LOW`, `0300`),
+/// a pair of them for a 16 bit value (`RESULT_HIGH:RESULT_LOW`), or a range of memory (`0300-030F`)
+/// addresses are in the same format as goto
+pub fn parse_watch(symbol_table: &SymbolTable, text: &str) -> Result<Watch, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err("Expected something to watch".to_string());
+    }
+    let target = if let Some(register) = Register::parse(text) {
+        WatchTarget::Register(register)
+    } else if let Some((high, low)) = text.split_once(':') {
+        WatchTarget::Pair {
+            high: memory_view::parse_address(symbol_table, high)?,
+            low: memory_view::parse_address(symbol_table, low)?,
+        }
+    } else if let Some((start, end)) = text.split_once('-') {
+        let (start, end) = (memory_view::parse_address(symbol_table, start)?, memory_view::parse_address(symbol_table, end)?);
+        if start > end {
+            return Err(format!("The range \"{text}\" is backwards"));
+        }
+        if end - start >= MAX_RANGE_LENGTH {
+            return Err(format!("Ranges can only be up to {MAX_RANGE_LENGTH:x} bytes long"));
+        }
+        WatchTarget::Range(start, end)
+    } else {
+        WatchTarget::Byte(memory_view::parse_address(symbol_table, text)?)
+    };
+    Ok(Watch { text: text.to_string(), target })
+}
+
+impl WatchTarget {
+    /// the bytes it's looking at, high byte first for 16 bit values
+    /// a single address reads `length` bytes from there, so it can be compared with a list of bytes
+    pub fn read(&self, cpu: &Processor, length: usize) -> Vec<u8> {
+        match *self {
+            WatchTarget::Register(Register::ProgramCounter) => cpu.program_counter.to_be_bytes().to_vec(),
+            WatchTarget::Register(Register::Accumulator) => vec![cpu.accumulator],
+            WatchTarget::Register(Register::General(index)) => vec![cpu.registers[index]],
+            WatchTarget::Register(Register::Status) => vec![cpu.status_register],
+            WatchTarget::Register(Register::StackPointer) => vec![cpu.stack_pointer],
+            WatchTarget::Byte(address) => (0..length.max(1) as u16).map(|offset| cpu.bus.peek(address.wrapping_add(offset))).collect(),
+            WatchTarget::Pair { high, low } => vec![cpu.bus.peek(high), cpu.bus.peek(low)],
+            WatchTarget::Range(start, end) => (start..=end).map(|address| cpu.bus.peek(address)).collect(),
+        }
+    }
+    /// the number of bytes it looks at, or None for a single address, which can be followed by any number of bytes
+    pub fn length(&self) -> Option<usize> {
+        match *self {
+            WatchTarget::Register(Register::ProgramCounter) | WatchTarget::Pair { .. } => Some(2),
+            WatchTarget::Register(_) => Some(1),
+            WatchTarget::Byte(_) => None,
+            WatchTarget::Range(start, end) => Some((end - start) as usize + 1),
+        }
+    }
+    /// sets the bytes, in the same order `read` gives them
+    pub fn write(&self, cpu: &mut Processor, bytes: &[u8]) {
+        match *self {
+            WatchTarget::Register(Register::ProgramCounter) => cpu.program_counter = u16::from_be_bytes([bytes[0], bytes[1]]),
+            WatchTarget::Register(Register::Accumulator) => cpu.accumulator = bytes[0],
+            WatchTarget::Register(Register::General(index)) => cpu.registers[index] = bytes[0],
+            WatchTarget::Register(Register::Status) => cpu.status_register = bytes[0],
+            WatchTarget::Register(Register::StackPointer) => cpu.stack_pointer = bytes[0],
+            WatchTarget::Byte(address) | WatchTarget::Range(address, _) => {
+                for (offset, byte) in bytes.iter().enumerate() {
+                    cpu.bus.write(address.wrapping_add(offset as u16), *byte);
+                }
+            }
+            WatchTarget::Pair { high, low } => {
+                cpu.bus.write(high, bytes[0]);
+                cpu.bus.write(low, bytes[1]);
+            }
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use crossterm::event::KeyCode;
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Paragraph, Wrap};
+use crate::simulator::executor::Processor;
+use crate::simulator::watch::{Watch, WatchTarget};
+
+/// a list of watches that are read again every time it's drawn, so they're always up to date with the last step
+#[derive(Debug, Default, Clone)]
+pub struct WatchPanel {
+    pub focused: bool,
+    watches: Vec<Watch>,
+    /// the watch delete removes
+    selected: usize,
+}
+impl WatchPanel {
+    pub fn add(&mut self, watch: Watch) {
+        self.watches.push(watch);
+        self.selected = self.watches.len() - 1;
+    }
+    /// up and down pick a watch, delete removes it
+    pub fn handle_key(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Esc | KeyCode::Char('e') => self.focused = false,
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(self.watches.len().saturating_sub(1)),
+            KeyCode::Delete | KeyCode::Backspace if self.selected < self.watches.len() => {
+                self.watches.remove(self.selected);
+                self.selected = self.selected.min(self.watches.len().saturating_sub(1));
+            }
+            _ => {}
+        }
+    }
+
+    pub fn widget(&self, cpu: &Processor) -> Paragraph<'static> {
+        let mut lines = Vec::with_capacity(self.watches.len());
+        for (index, watch) in self.watches.iter().enumerate() {
+            let mut name = Span::from(format!("{}: ", watch.text)).light_blue();
+            if self.focused && index == self.selected {
+                name = name.on_dark_gray();
+            }
+            let mut line = Line::from(name);
+            line.spans.extend(value_spans(cpu, watch.target));
+            lines.push(line);
+        }
+        if self.watches.is_empty() {
+            lines.push(Line::from("Nothing watched yet".dark_gray()));
+        }
+
+        let mut block = Block::bordered().title(" Watches ");
+        if self.focused {
+            block = block
+                .title_bottom(Line::from(vec![
+                    " Add ".into(), "<Enter>".blue().bold(),
+                    " Remove ".into(), "<Delete>".blue().bold(),
+                    " Leave ".into(), "<Escape> ".blue().bold(),
+                ]).centered())
+                .border_style(Style::new().blue());
+        }
+        Paragraph::new(lines)
+            .block(block)
+            .wrap(Wrap { trim: false })
+    }
+}
+
+/// the value in hex, decimal, signed decimal, and ascii (ranges are hex and ascii, like the memory pane)
+fn value_spans(cpu: &Processor, target: WatchTarget) -> Vec<Span<'static>> {
+    let values = target.read(cpu, 1);
+    match target {
+        WatchTarget::Range(..) => {
+            let hex = values.iter().map(|value| format!("{value:02x}")).collect::<Vec<String>>().join(" ");
+            let ascii = values.iter().map(|value| printable(*value)).collect::<String>();
+            vec![hex.yellow(), format!(" {ascii}").green()]
+        }
+        _ if values.len() == 2 => word_spans(u16::from_be_bytes([values[0], values[1]])),
+        _ => byte_spans(values[0]),
+    }
+}
+fn byte_spans(value: u8) -> Vec<Span<'static>> {
+    vec![
+        format!("{value:02x}").yellow(),
+        format!(" {value} {:+}", value as i8).into(),
+        format!(" '{}'", printable(value)).green(),
+    ]
+}
+fn word_spans(value: u16) -> Vec<Span<'static>> {
+    vec![
+        format!("{value:04x}").yellow(),
+        format!(" {value} {:+}", value as i16).into(),
+    ]
+}
+fn printable(value: u8) -> char {
+    if value.is_ascii_graphic() || value == b' ' { value as char } else { '.' }
+}